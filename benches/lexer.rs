@@ -0,0 +1,63 @@
+// lexes generated programs of growing size and reports the throughput, which should stay flat
+// as the input grows if lexing is linear. run with `cargo bench --bench lexer`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use simpl::lex::Lexer;
+
+// a chunk of source exercising every kind of token, including non-ascii names and text
+const CHUNK: &str = r#"
+/// A point in the plane.
+struct Point {
+    float x;
+    float y;
+}
+
+// distances are compared squared, which saves a square root
+fn distance_squared(Point a, Point b) -> float {
+    float dx = a.x - b.x; /* nested /* comments */ too */
+    float dy = a.y - b.y;
+    return dx * dx + dy * dy;
+}
+
+fn grüße(string name) -> string {
+    return "héllo, " + name + " \u{1F600}\n";
+}
+
+fn main() {
+    int größe = 0x7F + 0b1010 + 1_000_000;
+    while größe >= 10 {
+        größe = größe - 1;
+    }
+    print(grüße("wörld"), distance_squared(Point { x: 1.5, y: 2e3 }, Point { x: 0.0, y: -1.0 }));
+}
+"#;
+
+fn lex(source: &str) -> usize {
+    Lexer::new(source.to_string()).filter(Result::is_ok).count()
+}
+
+fn main() {
+    for megabytes in [1, 2, 4, 8] {
+        let source = CHUNK.repeat(megabytes * 1024 * 1024 / CHUNK.len() + 1);
+
+        // the fastest of a few runs is the least disturbed by everything else on the machine
+        let mut best = Duration::MAX;
+        let mut tokens = 0;
+        for _ in 0..5 {
+            let start = Instant::now();
+            tokens = black_box(lex(black_box(&source)));
+            best = best.min(start.elapsed());
+        }
+
+        let size = source.len() as f64 / (1024.0 * 1024.0);
+        println!(
+            "{:>5.1} MiB  {:>9} tokens  {:>8.2?}  {:>7.1} MiB/s",
+            size,
+            tokens,
+            best,
+            size / best.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,343 @@
+// the syntax tree produced by `parse` and consumed by the validator and interpreter.
+// every node carries the span of the source it was parsed from
+
+use std::fmt;
+
+use crate::lex::Span;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Program {
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Item {
+    Fn(FnDecl),
+    Struct(StructDecl),
+    Enum(EnumDecl),
+}
+
+// a name as written in the source
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+// fn name(type name, type name) -> type { ... }, or fn name(self, type name) -> type { ... } for a
+// method called on a value
+#[derive(Debug, PartialEq, Clone)]
+pub struct FnDecl {
+    // the `///` comment lines just before the function, joined with newlines
+    pub doc: Option<String>,
+    pub name: Ident,
+    // where `self` is declared, for methods that take it
+    pub receiver: Option<Span>,
+    pub params: Vec<Param>,
+    pub ret: TypeExpr,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Param {
+    pub ty: TypeExpr,
+    pub name: Ident,
+    pub span: Span,
+}
+
+// struct name { type name; fn name(...) -> type { ... } }
+#[derive(Debug, PartialEq, Clone)]
+pub struct StructDecl {
+    pub doc: Option<String>,
+    pub name: Ident,
+    pub fields: Vec<Field>,
+    pub methods: Vec<FnDecl>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field {
+    pub ty: TypeExpr,
+    pub name: Ident,
+    pub span: Span,
+}
+
+// enum name { Variant, Variant(type, type), Variant { name: type, name: type } }
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnumDecl {
+    pub doc: Option<String>,
+    pub name: Ident,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Variant {
+    pub name: Ident,
+    pub fields: VariantFields,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum VariantFields {
+    Unit,
+    Tuple(Vec<TypeExpr>),
+    Struct(Vec<Field>),
+}
+
+// a type annotation. a function without `-> type` gets a null return type spanning its signature
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub struct TypeExpr {
+    pub kind: Type,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub enum Type {
+    Int,
+    String,
+    Float,
+    Bool,
+    // list<T>
+    List(Box<TypeExpr>),
+    // map<K, V>
+    Map(Box<TypeExpr>, Box<TypeExpr>),
+    Null,
+    Named(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum StmtKind {
+    // type name = value;. only the bindings `lower` introduces leave out the type, which is then
+    // whatever the value is
+    Let {
+        ty: Option<TypeExpr>,
+        name: Ident,
+        value: Option<Expr>,
+    },
+    // target = value; where target is a variable or field
+    Assign {
+        target: Expr,
+        value: Expr,
+    },
+    // if cond { } elif cond { } else { }, with the branches in source order
+    If {
+        branches: Vec<(Expr, Block)>,
+        otherwise: Option<Block>,
+    },
+    While {
+        cond: Expr,
+        body: Block,
+    },
+    // for name in iter { }, over a list or a range. `lower` turns these into `while` loops
+    For {
+        var: Ident,
+        iter: Expr,
+        body: Block,
+    },
+    // match value { pattern => expr, pattern => { ... } }. only a statement `match` can have blocks
+    // as arms, since a block has no value
+    Match {
+        scrutinee: Expr,
+        arms: Vec<Arm>,
+    },
+    Return(Option<Expr>),
+    // only valid inside a loop, which the validator checks
+    Break,
+    Continue,
+    Block(Block),
+    Expr(Expr),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExprKind {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    Null,
+    Ident(String),
+    // [a, b, c]
+    List(Vec<Expr>),
+    // { key: value, key: value }
+    Map(Vec<(Expr, Expr)>),
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    Field {
+        expr: Box<Expr>,
+        field: Ident,
+    },
+    // expr[index], or expr[start..end] for a slice
+    Index {
+        expr: Box<Expr>,
+        index: Box<Expr>,
+    },
+    // start..end, or start..=end when `inclusive`
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+    },
+    // Name { field: value, field: value }, or Enum.Variant { field: value } for a variant with named
+    // fields. the other variants are built with what looks like a field access or a call,
+    // Enum.Variant and Enum.Variant(value), which the resolver tells apart from one
+    StructLit {
+        name: Ident,
+        variant: Option<Ident>,
+        fields: Vec<(Ident, Expr)>,
+    },
+    // match value { pattern => expr, ... }, where every arm is an expression
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<Arm>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub body: ArmBody,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ArmBody {
+    Expr(Expr),
+    Block(Block),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PatternKind {
+    // _
+    Wildcard,
+    // a name, which matches anything and binds it
+    Binding(Ident),
+    // an int, float, string, bool or null literal, or a negative number
+    Literal(Expr),
+    // Enum.Variant, Enum.Variant(pattern, ...) or Enum.Variant { field: pattern, ... }
+    Variant {
+        name: Ident,
+        variant: Ident,
+        fields: PatternFields,
+    },
+    // Name { field: pattern, ... }
+    Struct {
+        name: Ident,
+        fields: Vec<(Ident, Pattern)>,
+    },
+}
+
+// fields left out of a `{ ... }` pattern match anything, and `{ field }` is short for
+// `{ field: field }`
+#[derive(Debug, PartialEq, Clone)]
+pub enum PatternFields {
+    Unit,
+    Tuple(Vec<Pattern>),
+    Struct(Vec<(Ident, Pattern)>),
+}
+
+impl Pattern {
+    // whether the pattern matches every value of its type, without looking inside it
+    pub fn is_catch_all(&self) -> bool {
+        matches!(self.kind, PatternKind::Wildcard | PatternKind::Binding(_))
+    }
+}
+
+impl Expr {
+    // whether the expression names storage that can be changed in place: a variable, or a field or
+    // element of one
+    pub fn is_place(&self) -> bool {
+        match &self.kind {
+            ExprKind::Ident(_) => true,
+            ExprKind::Field { expr, .. } | ExprKind::Index { expr, .. } => expr.is_place(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOp::Neg => write!(f, "-"),
+            UnaryOp::Not => write!(f, "!"),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+        };
+        write!(f, "{}", op)
+    }
+}
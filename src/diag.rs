@@ -0,0 +1,448 @@
+// diagnostics shared by every stage: the lexer, parser, validator and interpreter all report
+// problems as a `Diagnostic`, which is rendered against the source like rustc does:
+//
+// error[E0001]: unknown field `z` on `MyStruct`
+//  --> example.spl:9:9
+//   |
+// 9 |         z: 1
+//   |         ^ unknown field
+//   |
+//   = help: `MyStruct` has the fields `x`, `y`
+
+use std::fmt::Write;
+use std::io::IsTerminal;
+
+use crate::ast::{EnumDecl, Field, FnDecl, Ident, Variant, VariantFields};
+use crate::lex::Span;
+
+// the error codes in use, so each kind of problem can be looked up regardless of its wording
+pub const UNKNOWN_FIELD: &str = "E0001";
+pub const SYNTAX_ERROR: &str = "E0002";
+pub const UNKNOWN_CHARACTER: &str = "E0003";
+pub const DUPLICATE_DEFINITION: &str = "E0004";
+pub const MISSING_MAIN: &str = "E0005";
+pub const MISSING_FIELD: &str = "E0006";
+pub const UNKNOWN_STRUCT: &str = "E0007";
+pub const MISMATCHED_TYPES: &str = "E0008";
+pub const WRONG_ARGUMENT_COUNT: &str = "E0009";
+pub const UNKNOWN_TYPE: &str = "E0010";
+pub const MISSING_RETURN: &str = "E0011";
+pub const UNSUPPORTED_OPERATION: &str = "E0012";
+pub const UNDEFINED_VARIABLE: &str = "E0013";
+pub const UNDEFINED_FUNCTION: &str = "E0014";
+pub const UNTERMINATED_STRING: &str = "E0015";
+pub const INVALID_ESCAPE: &str = "E0016";
+pub const LITERAL_OUT_OF_RANGE: &str = "E0017";
+pub const INVALID_LITERAL: &str = "E0018";
+pub const UNTERMINATED_COMMENT: &str = "E0019";
+pub const OUTSIDE_LOOP: &str = "E0020";
+pub const UNKNOWN_METHOD: &str = "E0021";
+pub const NON_EXHAUSTIVE_MATCH: &str = "E0022";
+pub const UNKNOWN_VARIANT: &str = "E0023";
+pub const UNINITIALIZED_VARIABLE: &str = "E0024";
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    // primary labels mark the problem itself, secondary ones point at related code
+    pub primary: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, message)
+    }
+
+    fn new(severity: Severity, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Diagnostic {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Diagnostic {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Diagnostic {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.help.push(help.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+// the candidate closest to a misspelled `name`, if one is near enough that it was probably meant
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    // a name so short that every character differs isn't a misspelling of anything
+    let len = name.chars().count();
+    let limit = len.div_ceil(3).min(len.saturating_sub(1));
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|&(d, _)| d <= limit)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, candidate)| candidate)
+}
+
+// the number of characters that have to be inserted, removed or replaced to turn `a` into `b`
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == cb { diagonal } else { 1 + diagonal.min(above).min(row[j]) };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+// the diagnostics both the validator and the type checker report, for names looked up on a struct
+// or enum that it doesn't have
+
+// a field that `owner`, declared at `declared_at`, doesn't have, suggesting the one that was
+// probably meant
+pub fn unknown_field(
+    owner: &str,
+    declared_at: Span,
+    fields: &[Field],
+    methods: &[FnDecl],
+    field: &Ident,
+) -> Diagnostic {
+    let mut diag = Diagnostic::error(format!("unknown field `{}` on `{}`", field.name, owner))
+        .with_code(UNKNOWN_FIELD)
+        .with_label(field.span, "unknown field")
+        .with_secondary(declared_at, format!("`{}` declared here", owner));
+
+    if methods.iter().any(|m| m.name.name == field.name) {
+        diag = diag.with_note(format!("`{}` is a method, so it must be called", field.name));
+    }
+    let names = fields.iter().map(|f| f.name.name.as_str());
+    if let Some(name) = closest(&field.name, names.clone()) {
+        diag.with_help(format!("did you mean `{}`?", name))
+    } else if fields.is_empty() {
+        diag.with_help(format!("`{}` has no fields", owner))
+    } else {
+        let list: Vec<String> = names.map(|f| format!("`{}`", f)).collect();
+        diag.with_help(format!("`{}` has the fields {}", owner, list.join(", ")))
+    }
+}
+
+// a variant that `decl` doesn't have, suggesting the one that was probably meant
+pub fn unknown_variant(decl: &EnumDecl, variant: &Ident) -> Diagnostic {
+    let owner = &decl.name.name;
+    let diag = Diagnostic::error(format!("no variant `{}` on `{}`", variant.name, owner))
+        .with_code(UNKNOWN_VARIANT)
+        .with_label(variant.span, "unknown variant")
+        .with_secondary(decl.name.span, format!("`{}` declared here", owner));
+
+    let names = decl.variants.iter().map(|v| v.name.name.as_str());
+    if let Some(name) = closest(&variant.name, names.clone()) {
+        diag.with_help(format!("did you mean `{}`?", name))
+    } else if decl.variants.is_empty() {
+        diag.with_help(format!("`{}` has no variants", owner))
+    } else {
+        let list: Vec<String> = names.map(|v| format!("`{}`", v)).collect();
+        diag.with_help(format!("`{}` has the variants {}", owner, list.join(", ")))
+    }
+}
+
+// a variant built or matched at `span` as if it had different fields than it was declared with
+pub fn wrong_shape(decl: &EnumDecl, variant: &Variant, span: Span) -> Diagnostic {
+    let name = format!("{}.{}", decl.name.name, variant.name.name);
+    let (fields, form) = match variant.fields {
+        VariantFields::Unit => ("no fields", name.clone()),
+        VariantFields::Tuple(_) => ("unnamed fields", format!("{}(...)", name)),
+        VariantFields::Struct(_) => ("named fields", format!("{} {{ ... }}", name)),
+    };
+    Diagnostic::error(format!("`{}` has {}", name, fields))
+        .with_code(MISMATCHED_TYPES)
+        .with_label(span, "doesn't match how the variant is declared")
+        .with_secondary(variant.span, format!("`{}` declared here", name))
+        .with_help(format!("write it as `{}`", form))
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    // auto colours only when stderr is a terminal and NO_COLOR is unset
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal(),
+        }
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+pub struct Renderer<'a> {
+    path: &'a str,
+    source: &'a str,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(path: &'a str, source: &'a str, color: ColorChoice) -> Renderer<'a> {
+        Renderer {
+            path,
+            source,
+            color: color.enabled(),
+        }
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn severity_style(severity: Severity) -> (&'static str, &'static str) {
+        match severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+        }
+    }
+
+    pub fn render(&self, diag: &Diagnostic) -> String {
+        let mut out = String::new();
+
+        let (name, style) = Renderer::severity_style(diag.severity);
+        let head = match diag.code {
+            Some(code) => format!("{}[{}]", name, code),
+            None => name.to_string(),
+        };
+        let _ = writeln!(out, "{}{}", self.paint(style, &head), self.paint(BOLD, &format!(": {}", diag.message)));
+
+        // labels are shown in source order, but the location header follows the first primary label
+        let mut labels: Vec<&Label> = diag.labels.iter().collect();
+        labels.sort_by_key(|label| label.span.offset);
+        let gutter = labels.iter().map(|l| l.span.line.to_string().len()).max().unwrap_or(0);
+        let pad = " ".repeat(gutter);
+        let bar = self.paint(BLUE, "|");
+
+        if let Some(main) = diag.labels.iter().find(|l| l.primary).or(diag.labels.first()) {
+            let _ = writeln!(out, "{}{} {}:{}", pad, self.paint(BLUE, "-->"), self.path, main.span);
+            let _ = writeln!(out, "{} {}", pad, bar);
+        }
+
+        let mut last_line = None;
+        for label in &labels {
+            let line = label.span.line;
+            let text = self.source.lines().nth(line.saturating_sub(1)).unwrap_or("");
+
+            if last_line != Some(line) {
+                if last_line.is_some_and(|last| line > last + 1) {
+                    let _ = writeln!(out, "{}", self.paint(BLUE, "..."));
+                }
+                let number = format!("{:>width$}", line, width = gutter);
+                let _ = writeln!(out, "{} {} {}", self.paint(BLUE, &number), bar, text);
+                last_line = Some(line);
+            }
+
+            // carets stop at the end of the line, and always show at least one. columns count
+            // characters, while offsets and lengths are in bytes
+            let start = text.char_indices().nth(label.span.column - 1).map_or(text.len(), |(i, _)| i);
+            let end = (start + label.span.len).min(text.len());
+            let width = text.get(start..end).map_or(0, |s| s.chars().count()).max(1);
+            let indent: String = text
+                .get(..start)
+                .unwrap_or("")
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let (mark, style) = if label.primary {
+                ("^", Renderer::severity_style(diag.severity).1)
+            } else {
+                ("-", BLUE)
+            };
+            let underline = format!("{} {}", mark.repeat(width), label.message);
+            let _ = writeln!(out, "{} {} {}{}", pad, bar, indent, self.paint(style, underline.trim_end()));
+        }
+
+        let has_footer = !diag.notes.is_empty() || !diag.help.is_empty();
+        if !diag.labels.is_empty() && has_footer {
+            let _ = writeln!(out, "{} {}", pad, bar);
+        }
+        for note in &diag.notes {
+            let _ = writeln!(out, "{} {} {}", pad, self.paint(BLUE, "="), self.paint(BOLD, "note:") + " " + note);
+        }
+        for help in &diag.help {
+            let _ = writeln!(out, "{} {} {}", pad, self.paint(BLUE, "="), self.paint(CYAN, "help:") + " " + help);
+        }
+
+        out
+    }
+
+    // print every diagnostic to stderr, followed by a summary when any of them are errors
+    pub fn emit(&self, diags: &[Diagnostic]) {
+        for diag in diags {
+            eprintln!("{}", self.render(diag));
+        }
+
+        let errors = diags.iter().filter(|d| d.is_error()).count();
+        if errors > 0 {
+            let plural = if errors == 1 { "" } else { "s" };
+            eprintln!(
+                "{}{}",
+                self.paint(RED, "error"),
+                self.paint(BOLD, &format!(": aborting due to {} previous error{}", errors, plural))
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "fn main() {\n\tint é = \"ünï\";\n\n\n\n\n\n\n\n\n  x = é;\n}";
+
+    // the span of `len` bytes at `line` and `column` of SOURCE
+    fn span(line: usize, column: usize, len: usize) -> Span {
+        let before: usize = SOURCE.lines().take(line - 1).map(|text| text.len() + 1).sum();
+        let text = SOURCE.lines().nth(line - 1).unwrap();
+        let offset = before + text.chars().take(column - 1).map(char::len_utf8).sum::<usize>();
+        Span { offset, line, column, len }
+    }
+
+    fn render(diag: &Diagnostic) -> String {
+        Renderer::new("a.spl", SOURCE, ColorChoice::Never).render(diag)
+    }
+
+    #[test]
+    fn labels_far_apart_are_separated() {
+        let diag = Diagnostic::error("mismatched types")
+            .with_code(MISMATCHED_TYPES)
+            .with_label(span(11, 7, 2), "expected `int`")
+            .with_secondary(span(2, 2, 3), "declared here")
+            .with_note("a note")
+            .with_help("some help");
+        let expected = "\
+error[E0008]: mismatched types
+  --> a.spl:11:7
+   |
+ 2 | \tint é = \"ünï\";
+   | \t--- declared here
+...
+11 |   x = é;
+   |       ^ expected `int`
+   |
+   = note: a note
+   = help: some help
+";
+        assert_eq!(render(&diag), expected);
+    }
+
+    #[test]
+    fn carets_count_characters() {
+        let diag = Diagnostic::warning("unused").with_label(span(2, 10, 7), "this string");
+        let expected = "\
+warning: unused
+ --> a.spl:2:10
+  |
+2 | \tint é = \"ünï\";
+  | \t        ^^^^^ this string
+";
+        assert_eq!(render(&diag), expected);
+
+        // a label running past the end of its line stops there, and an empty one still shows
+        let diag = Diagnostic::error("e").with_label(span(11, 7, 50), "").with_label(span(1, 12, 0), "end");
+        let rendered = render(&diag);
+        assert!(rendered.contains("\n   |       ^^\n"), "{}", rendered);
+        assert!(rendered.contains("\n   |            ^ end\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn colour_can_be_turned_off() {
+        let diag = Diagnostic::error("e").with_label(span(1, 1, 2), "here").with_note("n");
+        let plain = Renderer::new("a.spl", SOURCE, ColorChoice::Never).render(&diag);
+        let coloured = Renderer::new("a.spl", SOURCE, ColorChoice::Always).render(&diag);
+        assert!(!plain.contains('\x1b'));
+        assert!(coloured.starts_with(&format!("{}error{}", RED, RESET)));
+        assert!(coloured.contains(&format!("{}^^ here{}", RED, RESET)));
+    }
+
+    #[test]
+    fn only_near_names_are_suggested() {
+        let names = ["length", "len", "print", "x"];
+        assert_eq!(closest("lenght", names), Some("length"));
+        assert_eq!(closest("prnt", names), Some("print"));
+        assert_eq!(closest("lem", names), Some("len"));
+        assert_eq!(closest("lngth", names), Some("length"));
+        // a third of the name can be wrong, and never all of it
+        assert_eq!(closest("lenxxx", names), None);
+        assert_eq!(closest("y", names), None);
+        assert_eq!(closest("ab", ["ac"]), Some("ac"));
+        assert_eq!(closest("ab", ["cd"]), None);
+        assert_eq!(closest("prinnt", []), None);
+    }
+}
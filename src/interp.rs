@@ -0,0 +1,1149 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::ast::*;
+use crate::diag::Diagnostic;
+use crate::lex::Span;
+use crate::typeck::Promotions;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    List(Vec<Value>),
+    // kept sorted by key, so that printing and `keys` and `values` always give the same order
+    Map(BTreeMap<Key, Value>),
+    Struct {
+        name: String,
+        fields: Vec<(String, Value)>,
+    },
+    // a value of an enum. the fields of `Variant(a, b)` have no names, and those of
+    // `Variant { x: a }` all do
+    Variant {
+        name: String,
+        variant: String,
+        fields: Vec<(Option<String>, Value)>,
+    },
+    Range {
+        start: i64,
+        end: i64,
+        inclusive: bool,
+    },
+    Null,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            // debug formatting keeps the `.0` on whole floats
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Struct { name, fields } => {
+                write!(f, "{} {{ ", name)?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", field, value)?;
+                }
+                write!(f, " }}")
+            }
+            Value::Variant { name, variant, fields } => {
+                write!(f, "{}.{}", name, variant)?;
+                let named = match fields.first() {
+                    Some((field, _)) => field.is_some(),
+                    None => return Ok(()),
+                };
+                write!(f, "{}", if named { " { " } else { "(" })?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match field {
+                        Some(field) => write!(f, "{}: {}", field, value)?,
+                        None => write!(f, "{}", value)?,
+                    }
+                }
+                write!(f, "{}", if named { " }" } else { ")" })
+            }
+            Value::Range { start, end, inclusive } => {
+                write!(f, "{}..{}{}", start, if *inclusive { "=" } else { "" }, end)
+            }
+            Value::Null => write!(f, "null"),
+        }
+    }
+}
+
+// the values a map can be keyed by, which the type checker makes sure of
+#[derive(Debug, PartialEq, Clone, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+impl Key {
+    fn new(value: Value) -> Result<Key> {
+        match value {
+            Value::Bool(b) => Ok(Key::Bool(b)),
+            Value::Int(n) => Ok(Key::Int(n)),
+            Value::Str(s) => Ok(Key::Str(s)),
+            other => error(format!("{} can't be used as a map key", other)),
+        }
+    }
+
+    fn value(&self) -> Value {
+        match self {
+            Key::Bool(b) => Value::Bool(*b),
+            Key::Int(n) => Value::Int(*n),
+            Key::Str(s) => Value::Str(s.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuntimeError {
+    pub message: String,
+    // the innermost expression or statement being evaluated when the error happened
+    pub span: Option<Span>,
+}
+
+impl RuntimeError {
+    fn at(mut self, span: Span) -> RuntimeError {
+        self.span.get_or_insert(span);
+        self
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diag = Diagnostic::error(format!("runtime error: {}", self.message));
+        match self.span {
+            Some(span) => diag.with_label(span, "while evaluating this"),
+            None => diag,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+type Result<T> = std::result::Result<T, RuntimeError>;
+
+fn error<T>(message: impl Into<String>) -> Result<T> {
+    Err(RuntimeError {
+        message: message.into(),
+        span: None,
+    })
+}
+
+// how control leaves a block
+enum Flow {
+    Normal,
+    Return(Value),
+    Break,
+    Continue,
+}
+
+pub struct Interpreter<'a> {
+    functions: HashMap<&'a str, &'a FnDecl>,
+    structs: HashMap<&'a str, &'a StructDecl>,
+    enums: HashMap<&'a str, &'a EnumDecl>,
+    scopes: Vec<HashMap<String, Value>>,
+    promoted: &'a Promotions,
+    // how many calls are running, which is at most MAX_DEPTH
+    depth: usize,
+}
+
+// the most calls that can be running at once. each one takes a few frames of the interpreter's own
+// stack per statement and expression it is nested in, which STACK_SIZE leaves plenty of room for
+const MAX_DEPTH: usize = 1_000;
+const STACK_SIZE: usize = 1 << 30;
+
+// run a checked program, starting at its `main` function, with the ints the checker found used as
+// floats. the program gets a thread of its own, since the main thread's stack would only hold a few
+// hundred calls
+pub fn run(program: &Program, promoted: &Promotions) -> Result<Value> {
+    std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, || {
+            let mut interp = Interpreter::new(program, promoted)?;
+            interp.call("main", Vec::new())
+        });
+        match thread.map(|thread| thread.join()) {
+            Ok(Ok(result)) => result,
+            Ok(Err(panic)) => std::panic::resume_unwind(panic),
+            Err(err) => error(format!("could not start the program: {}", err)),
+        }
+    })
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(program: &'a Program, promoted: &'a Promotions) -> Result<Interpreter<'a>> {
+        let mut interp = Interpreter {
+            functions: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            scopes: Vec::new(),
+            promoted,
+            depth: 0,
+        };
+
+        for item in &program.items {
+            match item {
+                Item::Fn(function) => {
+                    if interp.functions.insert(&function.name.name, function).is_some() {
+                        return error(format!("function `{}` is defined more than once", function.name.name))
+                            .map_err(|e| e.at(function.name.span));
+                    }
+                }
+                Item::Struct(decl) => {
+                    interp.structs.insert(&decl.name.name, decl);
+                }
+                Item::Enum(decl) => {
+                    interp.enums.insert(&decl.name.name, decl);
+                }
+            }
+        }
+
+        Ok(interp)
+    }
+
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value> {
+        if let Some(value) = self.call_builtin(name, &args)? {
+            return Ok(value);
+        }
+
+        let Some(&function) = self.functions.get(name) else {
+            return error(format!("call to undefined function `{}`", name));
+        };
+        let (value, _) = self.invoke(function, None, args)?;
+        Ok(value)
+    }
+
+    // run a function or method, returning its result and, for a method, what `self` ended up as
+    fn invoke(
+        &mut self,
+        function: &FnDecl,
+        receiver: Option<Value>,
+        args: Vec<Value>,
+    ) -> Result<(Value, Option<Value>)> {
+        let name = &function.name.name;
+        if function.params.len() != args.len() {
+            return error(format!(
+                "`{}` takes {} argument(s) but {} were given",
+                name,
+                function.params.len(),
+                args.len()
+            ));
+        }
+
+        let mut scope: HashMap<String, Value> = function
+            .params
+            .iter()
+            .zip(args)
+            .map(|(param, arg)| (param.name.name.clone(), arg))
+            .collect();
+        if let Some(receiver) = receiver {
+            scope.insert("self".to_string(), receiver);
+        }
+
+        // recursing without end would otherwise overflow the interpreter's own stack
+        if self.depth == MAX_DEPTH {
+            return error(format!("stack overflow: more than {} nested calls", MAX_DEPTH));
+        }
+
+        // a call only sees its own arguments and locals
+        let caller = std::mem::replace(&mut self.scopes, vec![scope]);
+        self.depth += 1;
+        let flow = self.exec_block(&function.body);
+        self.depth -= 1;
+        let mut callee = std::mem::replace(&mut self.scopes, caller);
+        let receiver = callee.first_mut().and_then(|scope| scope.remove("self"));
+
+        let value = match flow? {
+            Flow::Return(value) => value,
+            Flow::Normal => Value::Null,
+            Flow::Break | Flow::Continue => return error("`break` or `continue` outside of a loop"),
+        };
+        Ok((value, receiver))
+    }
+
+    // `object.name(args)`, where `object` is either a struct value or the name of a struct
+    fn call_method(&mut self, object: &Expr, name: &str, args: &[Expr]) -> Result<Value> {
+        // the name of a struct calls one of its associated functions, which don't take `self`
+        if let ExprKind::Ident(owner) = &object.kind {
+            let shadowed = self.scopes.iter().any(|scope| scope.contains_key(owner));
+            if let (false, Some(&decl)) = (shadowed, self.structs.get(owner.as_str())) {
+                let function = method(decl, name)?;
+                let args = args.iter().map(|arg| self.eval(arg)).collect::<Result<Vec<_>>>()?;
+                let (value, _) = self.invoke(function, None, args)?;
+                return Ok(value);
+            }
+        }
+
+        // the receiver's place is worked out once, so that its indexes aren't run again to write it back
+        let path = match object.is_place() {
+            true => Some(self.path(object)?),
+            false => None,
+        };
+        let receiver = match &path {
+            Some((var, steps)) => self.lookup(var, steps).map_err(|e| e.at(object.span))?.into_owned(),
+            None => self.eval(object)?,
+        };
+        let decl = match &receiver {
+            Value::Struct { name: owner, .. } => match self.structs.get(owner.as_str()) {
+                Some(&decl) => decl,
+                None => return error(format!("`{}` is not a struct", owner)),
+            },
+            other => return error(format!("cannot call method `{}` on {}", name, other)),
+        };
+        let function = method(decl, name)?;
+        let args = args.iter().map(|arg| self.eval(arg)).collect::<Result<Vec<_>>>()?;
+        let (value, receiver) = self.invoke(function, Some(receiver), args)?;
+
+        // changes a method makes to `self` are kept when it was called on a variable, field or element
+        if let (Some(receiver), Some((var, steps))) = (receiver, path) {
+            *self.slot(&var, &steps).map_err(|e| e.at(object.span))? = receiver;
+        }
+        Ok(value)
+    }
+
+    fn call_builtin(&mut self, name: &str, args: &[Value]) -> Result<Option<Value>> {
+        match (name, args) {
+            ("print_int", [Value::Int(n)]) => println!("{}", n),
+            ("print_int", [other]) => return error(format!("print_int expects an int, found {}", other)),
+            // truncating towards zero, and saturating at the ends of the int range
+            ("to_int", [Value::Float(x)]) => return Ok(Some(Value::Int(*x as i64))),
+            ("to_int", [Value::Int(n)]) => return Ok(Some(Value::Int(*n))),
+            ("to_int", [other]) => return error(format!("to_int expects a float, found {}", other)),
+            (name, [collection, rest @ ..]) if READERS.contains(&name) => return read(name, collection, rest).map(Some),
+            ("print", args) => {
+                let text: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                println!("{}", text.join(" "));
+            }
+            _ => return Ok(None),
+        }
+        Ok(Some(Value::Null))
+    }
+
+    fn exec_block(&mut self, block: &Block) -> Result<Flow> {
+        self.scopes.push(HashMap::new());
+        let flow = self.exec_statements(&block.stmts);
+        self.scopes.pop();
+        flow
+    }
+
+    fn exec_statements(&mut self, stmts: &[Stmt]) -> Result<Flow> {
+        for stmt in stmts {
+            match self.exec_stmt(stmt)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec_stmt(&mut self, stmt: &Stmt) -> Result<Flow> {
+        self.exec_stmt_kind(&stmt.kind).map_err(|e| e.at(stmt.span))
+    }
+
+    fn exec_stmt_kind(&mut self, stmt: &StmtKind) -> Result<Flow> {
+        match stmt {
+            StmtKind::Let { name, value, .. } => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Null,
+                };
+                self.scopes.last_mut().unwrap().insert(name.name.clone(), value);
+            }
+            StmtKind::Assign { target, value } => {
+                let value = self.eval(value)?;
+                *self.place(target)? = value;
+            }
+            StmtKind::If {
+                branches,
+                otherwise,
+            } => {
+                for (cond, body) in branches {
+                    if self.eval_bool(cond)? {
+                        return self.exec_block(body);
+                    }
+                }
+                if let Some(body) = otherwise {
+                    return self.exec_block(body);
+                }
+            }
+            StmtKind::While { cond, body } => {
+                while self.eval_bool(cond)? {
+                    match self.exec_block(body)? {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                    }
+                }
+            }
+            StmtKind::For { .. } => return error("`for` loops must be lowered before they are run"),
+            StmtKind::Match { scrutinee, arms } => {
+                let value = self.eval(scrutinee)?;
+                let (arm, bindings) = self.select(arms, &value)?;
+                self.scopes.push(bindings);
+                let flow = match &arm.body {
+                    ArmBody::Expr(expr) => self.eval(expr).map(|_| Flow::Normal),
+                    ArmBody::Block(body) => self.exec_statements(&body.stmts),
+                };
+                self.scopes.pop();
+                return flow;
+            }
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Null,
+                };
+                return Ok(Flow::Return(value));
+            }
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+            StmtKind::Block(block) => return self.exec_block(block),
+            StmtKind::Expr(expr) => {
+                self.eval(expr)?;
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    // the storage an assignment writes to: a variable, or a field or element of one
+    fn place(&mut self, target: &Expr) -> Result<&mut Value> {
+        let (var, steps) = self.path(target)?;
+        self.slot(&var, &steps).map_err(|e| e.at(target.span))
+    }
+
+    // the variable `target` names storage in, and the steps from it to that storage. any indexes
+    // are evaluated here, once, so the storage can be read or written without running them again
+    fn path(&mut self, target: &Expr) -> Result<(String, Vec<Step>)> {
+        let span = target.span;
+        self.path_kind(&target.kind).map_err(|e| e.at(span))
+    }
+
+    fn path_kind(&mut self, target: &ExprKind) -> Result<(String, Vec<Step>)> {
+        match target {
+            ExprKind::Ident(name) => Ok((name.clone(), Vec::new())),
+            ExprKind::Field { expr, field } => {
+                let (var, mut steps) = self.path(expr)?;
+                steps.push(Step::Field(field.name.clone()));
+                Ok((var, steps))
+            }
+            ExprKind::Index { expr, index } => {
+                let (var, mut steps) = self.path(expr)?;
+                steps.push(Step::Index(self.eval(index)?));
+                Ok((var, steps))
+            }
+            _ => error("expected a variable, field or element"),
+        }
+    }
+
+    // the value at the end of a path, which is only copied when part of it is a slice
+    fn lookup(&self, var: &str, steps: &[Step]) -> Result<Cow<'_, Value>> {
+        let Some(mut value) = self.scopes.iter().rev().find_map(|scope| scope.get(var)).map(Cow::Borrowed) else {
+            return error(format!("use of undefined variable `{}`", var));
+        };
+        for step in steps {
+            value = match value {
+                Cow::Borrowed(value) => part(value, step)?,
+                Cow::Owned(value) => Cow::Owned(part(&value, step)?.into_owned()),
+            };
+        }
+        Ok(value)
+    }
+
+    // the storage at the end of a path. assigning to a missing map key adds it
+    fn slot(&mut self, var: &str, steps: &[Step]) -> Result<&mut Value> {
+        let Some(mut value) = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(var)) else {
+            return error(format!("assignment to undefined variable `{}`", var));
+        };
+        for step in steps {
+            value = match (value, step) {
+                (Value::Struct { name, fields }, Step::Field(field)) => {
+                    match fields.iter_mut().find(|(f, _)| f == field) {
+                        Some((_, value)) => value,
+                        None => return error(format!("`{}` has no field `{}`", name, field)),
+                    }
+                }
+                (Value::List(items), Step::Index(Value::Int(i))) => {
+                    let i = element(*i, items.len())?;
+                    &mut items[i]
+                }
+                (Value::List(_), Step::Index(Value::Range { .. })) => return error("cannot assign to a slice"),
+                (Value::Map(entries), Step::Index(key)) => entries.entry(Key::new(key.clone())?).or_insert(Value::Null),
+                (other, Step::Field(field)) => return error(format!("cannot access field `{}` of {}", field, other)),
+                (other, Step::Index(index)) => return error(format!("cannot index {} with {}", other, index)),
+            };
+        }
+        Ok(value)
+    }
+
+    // the built-ins that only look at a list or map, which is read where it is rather than copied
+    fn call_reader(&mut self, name: &str, args: &[Expr]) -> Result<Value> {
+        let Some((target, rest)) = args.split_first() else {
+            return error(format!("{} expects a list or map", name));
+        };
+        let (var, steps) = self.path(target)?;
+        let rest = rest.iter().map(|arg| self.eval(arg)).collect::<Result<Vec<_>>>()?;
+        read(name, &*self.lookup(&var, &steps)?, &rest)
+    }
+
+    // the built-ins that change a list or map in place, through the variable, field or element
+    // holding it
+    fn call_mutator(&mut self, name: &str, args: &[Expr]) -> Result<Value> {
+        let Some((target, rest)) = args.split_first() else {
+            return error(format!("{} expects a list", name));
+        };
+        // arguments run left to right, so the target's indexes come before the values
+        let (var, steps) = self.path(target)?;
+        let rest = rest.iter().map(|arg| self.eval(arg)).collect::<Result<Vec<_>>>()?;
+        let slot = self.slot(&var, &steps).map_err(|e| e.at(target.span))?;
+        let items = match (slot, rest.as_slice()) {
+            (Value::List(items), _) => items,
+            (Value::Map(entries), [key]) if name == "remove" => {
+                let key = Key::new(key.clone())?;
+                return match entries.remove(&key) {
+                    Some(value) => Ok(value),
+                    None => error(format!("key {} is not in the map", key)),
+                };
+            }
+            (other, _) => return error(format!("{} expects a list, found {}", name, other)),
+        };
+
+        match (name, rest.as_slice()) {
+            ("push", [value]) => items.push(value.clone()),
+            ("pop", []) => return items.pop().map_or_else(|| error("cannot pop from an empty list"), Ok),
+            ("insert", [Value::Int(i), value]) => {
+                // inserting at the length appends
+                if *i < 0 || *i as usize > items.len() {
+                    return error(format!("cannot insert at {} in a list of length {}", i, items.len()));
+                }
+                items.insert(*i as usize, value.clone());
+            }
+            ("remove", [Value::Int(i)]) => {
+                let i = element(*i, items.len())?;
+                return Ok(items.remove(i));
+            }
+            _ => return error(format!("wrong arguments to {}", name)),
+        }
+        Ok(Value::Null)
+    }
+
+    fn eval_bool(&mut self, expr: &Expr) -> Result<bool> {
+        match self.eval(expr)? {
+            Value::Bool(b) => Ok(b),
+            other => error(format!("expected a bool, found {}", other)),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value> {
+        match self.eval_kind(&expr.kind).map_err(|e| e.at(expr.span))? {
+            // an int stored or used where the checker expects a float, which it becomes here
+            Value::Int(n) if self.promoted.contains(&expr.span) => Ok(Value::Float(n as f64)),
+            value => Ok(value),
+        }
+    }
+
+    fn eval_kind(&mut self, expr: &ExprKind) -> Result<Value> {
+        match expr {
+            ExprKind::Int(n) => Ok(Value::Int(*n)),
+            ExprKind::Float(x) => Ok(Value::Float(*x)),
+            ExprKind::Bool(b) => Ok(Value::Bool(*b)),
+            ExprKind::Str(s) => Ok(Value::Str(s.clone())),
+            ExprKind::Null => Ok(Value::Null),
+            ExprKind::Ident(name) => match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+                Some(value) => Ok(value.clone()),
+                None => error(format!("use of undefined variable `{}`", name)),
+            },
+            ExprKind::List(items) => Ok(Value::List(items.iter().map(|item| self.eval(item)).collect::<Result<_>>()?)),
+            ExprKind::Map(entries) => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    let key = Key::new(self.eval(key)?)?;
+                    map.insert(key, self.eval(value)?);
+                }
+                Ok(Value::Map(map))
+            }
+            ExprKind::Unary { op, expr } => match (op, self.eval(expr)?) {
+                (UnaryOp::Neg, Value::Int(n)) => match n.checked_neg() {
+                    Some(n) => Ok(Value::Int(n)),
+                    None => error(format!("integer overflow in `-({})`", n)),
+                },
+                (UnaryOp::Neg, Value::Float(x)) => Ok(Value::Float(-x)),
+                (UnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                (op, other) => error(format!("cannot apply `{}` to {}", op, other)),
+            },
+            ExprKind::Binary {
+                op: op @ (BinaryOp::And | BinaryOp::Or),
+                lhs,
+                rhs,
+            } => {
+                let lhs = self.eval_bool(lhs)?;
+                if lhs == (*op == BinaryOp::Or) {
+                    return Ok(Value::Bool(lhs));
+                }
+                Ok(Value::Bool(self.eval_bool(rhs)?))
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                binary(*op, lhs, rhs)
+            }
+            ExprKind::Call { callee, args } => {
+                let name = match &callee.kind {
+                    ExprKind::Ident(name) => name,
+                    ExprKind::Field { expr: object, field } => match self.enum_named(object) {
+                        Some(decl) => return self.eval_variant(decl, field, args),
+                        None => return self.call_method(object, &field.name, args),
+                    },
+                    _ => return error("only functions and methods can be called"),
+                };
+                if MUTATORS.contains(&name.as_str()) {
+                    return self.call_mutator(name, args);
+                }
+                if READERS.contains(&name.as_str()) && args.first().is_some_and(Expr::is_place) {
+                    return self.call_reader(name, args);
+                }
+                let args = args.iter().map(|arg| self.eval(arg)).collect::<Result<Vec<_>>>()?;
+                self.call(name, args)
+            }
+            ExprKind::Field { expr, field } => {
+                if let Some(decl) = self.enum_named(expr) {
+                    return self.eval_variant(decl, field, &[]);
+                }
+                let step = Step::Field(field.name.clone());
+                // a field of a variable is read where it is, rather than copying the whole variable
+                if expr.is_place() {
+                    let (var, steps) = self.path(expr)?;
+                    return Ok(part(&*self.lookup(&var, &steps)?, &step)?.into_owned());
+                }
+                let value = self.eval(expr)?;
+                Ok(part(&value, &step)?.into_owned())
+            }
+            // likewise an element, which would otherwise make indexing as slow as the list is long
+            ExprKind::Index { expr, index } => {
+                if expr.is_place() {
+                    let (var, steps) = self.path(expr)?;
+                    let step = Step::Index(self.eval(index)?);
+                    return Ok(part(&*self.lookup(&var, &steps)?, &step)?.into_owned());
+                }
+                let value = self.eval(expr)?;
+                let step = Step::Index(self.eval(index)?);
+                Ok(part(&value, &step)?.into_owned())
+            }
+            ExprKind::Range { start, end, inclusive } => match (self.eval(start)?, self.eval(end)?) {
+                (Value::Int(start), Value::Int(end)) => Ok(Value::Range {
+                    start,
+                    end,
+                    inclusive: *inclusive,
+                }),
+                (start, end) => error(format!("the bounds of a range must be ints, found {} and {}", start, end)),
+            },
+            ExprKind::StructLit {
+                name,
+                variant: None,
+                fields,
+            } => {
+                let Some(&decl) = self.structs.get(name.name.as_str()) else {
+                    return error(format!("`{}` is not a struct", name.name));
+                };
+                Ok(Value::Struct {
+                    name: name.name.clone(),
+                    fields: self.eval_fields(&name.name, &decl.fields, fields)?,
+                })
+            }
+            ExprKind::StructLit {
+                name,
+                variant: Some(variant),
+                fields,
+            } => {
+                let Some(&decl) = self.enums.get(name.name.as_str()) else {
+                    return error(format!("`{}` is not an enum", name.name));
+                };
+                let declared = match &decl_variant(decl, &variant.name)?.fields {
+                    VariantFields::Struct(declared) => declared,
+                    _ => return error(format!("`{}.{}` has no named fields", name.name, variant.name)),
+                };
+                let owner = format!("{}.{}", name.name, variant.name);
+                let fields = self.eval_fields(&owner, declared, fields)?;
+                Ok(Value::Variant {
+                    name: name.name.clone(),
+                    variant: variant.name.clone(),
+                    fields: fields.into_iter().map(|(field, value)| (Some(field), value)).collect(),
+                })
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let value = self.eval(scrutinee)?;
+                let (arm, bindings) = self.select(arms, &value)?;
+                let ArmBody::Expr(expr) = &arm.body else {
+                    return error("a `match` used as a value can't have blocks as arms");
+                };
+                self.scopes.push(bindings);
+                let value = self.eval(expr);
+                self.scopes.pop();
+                value
+            }
+        }
+    }
+
+    // the values of the fields of `owner` given in a literal, in declaration order so that they
+    // print consistently
+    fn eval_fields(
+        &mut self,
+        owner: &str,
+        declared: &[Field],
+        given: &[(Ident, Expr)],
+    ) -> Result<Vec<(String, Value)>> {
+        if let Some((field, _)) = given.iter().find(|(f, _)| !declared.iter().any(|d| d.name.name == f.name)) {
+            return error(format!("`{}` has no field `{}`", owner, field.name)).map_err(|e| e.at(field.span));
+        }
+
+        let mut fields = Vec::new();
+        for declared in declared {
+            let field = &declared.name.name;
+            let Some((_, value)) = given.iter().find(|(f, _)| f.name == *field) else {
+                return error(format!("missing field `{}` in `{}` literal", field, owner));
+            };
+            fields.push((field.clone(), self.eval(value)?));
+        }
+        Ok(fields)
+    }
+
+    // the enum that `object` names in `Enum.Variant`, unless a variable has the same name
+    fn enum_named(&self, object: &Expr) -> Option<&'a EnumDecl> {
+        let ExprKind::Ident(name) = &object.kind else {
+            return None;
+        };
+        if self.scopes.iter().any(|scope| scope.contains_key(name)) {
+            return None;
+        }
+        self.enums.get(name.as_str()).copied()
+    }
+
+    // `Enum.Variant`, or `Enum.Variant(args)` for a variant with unnamed fields
+    fn eval_variant(&mut self, decl: &EnumDecl, name: &Ident, args: &[Expr]) -> Result<Value> {
+        let types: &[TypeExpr] = match &decl_variant(decl, &name.name)?.fields {
+            VariantFields::Unit => &[],
+            VariantFields::Tuple(types) => types,
+            VariantFields::Struct(_) => {
+                return error(format!("`{}.{}` has named fields", decl.name.name, name.name));
+            }
+        };
+        if types.len() != args.len() {
+            return error(format!(
+                "`{}.{}` has {} field(s) but {} were given",
+                decl.name.name,
+                name.name,
+                types.len(),
+                args.len()
+            ));
+        }
+        let mut fields = Vec::new();
+        for arg in args {
+            fields.push((None, self.eval(arg)?));
+        }
+        Ok(Value::Variant {
+            name: decl.name.name.clone(),
+            variant: name.name.clone(),
+            fields,
+        })
+    }
+
+    // the first arm whose pattern matches `value`, and the variables its pattern binds
+    fn select<'b>(&mut self, arms: &'b [Arm], value: &Value) -> Result<(&'b Arm, HashMap<String, Value>)> {
+        for arm in arms {
+            let mut bindings = HashMap::new();
+            if self.matches(&arm.pattern, value, &mut bindings)? {
+                return Ok((arm, bindings));
+            }
+        }
+        error(format!("no arm of the `match` matches {}", value))
+    }
+
+    fn matches(&mut self, pattern: &Pattern, value: &Value, bindings: &mut HashMap<String, Value>) -> Result<bool> {
+        match (&pattern.kind, value) {
+            (PatternKind::Wildcard, _) => Ok(true),
+            (PatternKind::Binding(name), value) => {
+                bindings.insert(name.name.clone(), value.clone());
+                Ok(true)
+            }
+            (PatternKind::Literal(expr), value) => {
+                let literal = self.eval(expr)?;
+                Ok(binary(BinaryOp::Eq, literal, value.clone())? == Value::Bool(true))
+            }
+            (
+                PatternKind::Variant {
+                    name,
+                    variant,
+                    fields: patterns,
+                },
+                Value::Variant {
+                    name: owner,
+                    variant: built,
+                    fields,
+                },
+            ) => {
+                if name.name != *owner || variant.name != *built {
+                    return Ok(false);
+                }
+                match patterns {
+                    PatternFields::Unit => Ok(true),
+                    PatternFields::Tuple(patterns) => {
+                        for (pattern, (_, value)) in patterns.iter().zip(fields) {
+                            if !self.matches(pattern, value, bindings)? {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    }
+                    PatternFields::Struct(patterns) => {
+                        let fields = fields.iter().filter_map(|(field, value)| Some((field.as_deref()?, value)));
+                        self.matches_fields(patterns, fields.collect(), bindings)
+                    }
+                }
+            }
+            (PatternKind::Struct { name, fields: patterns }, Value::Struct { name: owner, fields }) => {
+                if name.name != *owner {
+                    return Ok(false);
+                }
+                let fields = fields.iter().map(|(field, value)| (field.as_str(), value));
+                self.matches_fields(patterns, fields.collect(), bindings)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn matches_fields(
+        &mut self,
+        patterns: &[(Ident, Pattern)],
+        fields: Vec<(&str, &Value)>,
+        bindings: &mut HashMap<String, Value>,
+    ) -> Result<bool> {
+        for (field, pattern) in patterns {
+            let Some((_, value)) = fields.iter().find(|(name, _)| *name == field.name) else {
+                return error(format!("no field `{}` to match", field.name));
+            };
+            if !self.matches(pattern, value, bindings)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+// the built-ins that change their first argument, which is passed as a place rather than a value
+const MUTATORS: &[&str] = &["push", "pop", "insert", "remove"];
+
+// the built-ins that only read the list or map they are given
+const READERS: &[&str] = &["len", "contains", "keys", "values"];
+
+fn read(name: &str, collection: &Value, rest: &[Value]) -> Result<Value> {
+    match (name, collection, rest) {
+        ("len", Value::List(items), []) => Ok(Value::Int(items.len() as i64)),
+        ("len", Value::Map(entries), []) => Ok(Value::Int(entries.len() as i64)),
+        ("len", other, _) => error(format!("len expects a list or map, found {}", other)),
+        ("contains", Value::List(items), [value]) => Ok(Value::Bool(items.contains(value))),
+        ("contains", Value::Map(entries), [key]) => Ok(Value::Bool(entries.contains_key(&Key::new(key.clone())?))),
+        ("keys", Value::Map(entries), []) => Ok(Value::List(entries.keys().map(Key::value).collect())),
+        ("values", Value::Map(entries), []) => Ok(Value::List(entries.values().cloned().collect())),
+        (name, other, _) => error(format!("wrong arguments to {}, found {}", name, other)),
+    }
+}
+
+// a step from a value to one of its parts: a field of a struct, or an element or slice of a list
+// or an entry of a map
+enum Step {
+    Field(String),
+    Index(Value),
+}
+
+fn part<'v>(value: &'v Value, step: &Step) -> Result<Cow<'v, Value>> {
+    match (value, step) {
+        (Value::Struct { name, fields }, Step::Field(field)) => match fields.iter().find(|(f, _)| f == field) {
+            Some((_, value)) => Ok(Cow::Borrowed(value)),
+            None => error(format!("`{}` has no field `{}`", name, field)),
+        },
+        (Value::List(items), Step::Index(Value::Int(i))) => Ok(Cow::Borrowed(&items[element(*i, items.len())?])),
+        (Value::List(items), Step::Index(range @ Value::Range { start, end, inclusive })) => {
+            let (start, end) = (*start, if *inclusive { end.saturating_add(1) } else { *end });
+            if start > end {
+                return error(format!("slice {} starts after it ends", range));
+            }
+            if start < 0 || end as usize > items.len() {
+                return error(format!("slice {} is out of bounds for a list of length {}", range, items.len()));
+            }
+            Ok(Cow::Owned(Value::List(items[start as usize..end as usize].to_vec())))
+        }
+        (Value::Map(entries), Step::Index(key)) => {
+            let key = Key::new(key.clone())?;
+            match entries.get(&key) {
+                Some(value) => Ok(Cow::Borrowed(value)),
+                None => error(format!("key {} is not in the map", key)),
+            }
+        }
+        (other, Step::Field(field)) => error(format!("cannot access field `{}` of {}", field, other)),
+        (other, Step::Index(index)) => error(format!("cannot index {} with {}", other, index)),
+    }
+}
+
+fn method<'a>(decl: &'a StructDecl, name: &str) -> Result<&'a FnDecl> {
+    match decl.methods.iter().find(|m| m.name.name == name) {
+        Some(method) => Ok(method),
+        None => error(format!("`{}` has no method `{}`", decl.name.name, name)),
+    }
+}
+
+fn decl_variant<'a>(decl: &'a EnumDecl, name: &str) -> Result<&'a Variant> {
+    match decl.variants.iter().find(|v| v.name.name == name) {
+        Some(variant) => Ok(variant),
+        None => error(format!("`{}` has no variant `{}`", decl.name.name, name)),
+    }
+}
+
+// the position of element `index` in a list of length `len`, if there is one
+fn element(index: i64, len: usize) -> Result<usize> {
+    if index < 0 || index as usize >= len {
+        return error(format!("index {} is out of bounds for a list of length {}", index, len));
+    }
+    Ok(index as usize)
+}
+
+// ints stored where a float is declared become floats, as the type checker allows
+// `&&` and `||` only evaluate their right side when they need to, so `eval_kind` handles them
+fn binary(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value> {
+    // an int used with a float is promoted first
+    let (lhs, rhs) = match (lhs, rhs) {
+        (Value::Int(a), Value::Float(b)) => (Value::Float(a as f64), Value::Float(b)),
+        (Value::Float(a), Value::Int(b)) => (Value::Float(a), Value::Float(b as f64)),
+        operands => operands,
+    };
+
+    match (op, lhs, rhs) {
+        (BinaryOp::Eq, lhs, rhs) => Ok(Value::Bool(lhs == rhs)),
+        (BinaryOp::Ne, lhs, rhs) => Ok(Value::Bool(lhs != rhs)),
+        (BinaryOp::Add, Value::List(mut a), Value::List(b)) => {
+            a.extend(b);
+            Ok(Value::List(a))
+        }
+        (op, Value::Int(a), Value::Int(b)) => {
+            let result = match op {
+                BinaryOp::Add => a.checked_add(b),
+                BinaryOp::Sub => a.checked_sub(b),
+                BinaryOp::Mul => a.checked_mul(b),
+                BinaryOp::Div if b == 0 => return error("division by zero"),
+                BinaryOp::Div => a.checked_div(b),
+                BinaryOp::Lt => return Ok(Value::Bool(a < b)),
+                BinaryOp::Le => return Ok(Value::Bool(a <= b)),
+                BinaryOp::Gt => return Ok(Value::Bool(a > b)),
+                BinaryOp::Ge => return Ok(Value::Bool(a >= b)),
+                BinaryOp::Eq | BinaryOp::Ne | BinaryOp::And | BinaryOp::Or => unreachable!(),
+            };
+            // int arithmetic traps instead of wrapping when the result doesn't fit in 64 bits
+            match result {
+                Some(n) => Ok(Value::Int(n)),
+                None => error(format!("integer overflow in `{} {} {}`", a, op, b)),
+            }
+        }
+        // float division by zero follows IEEE 754 and gives an infinity or NaN
+        (op, Value::Float(a), Value::Float(b)) => Ok(match op {
+            BinaryOp::Add => Value::Float(a + b),
+            BinaryOp::Sub => Value::Float(a - b),
+            BinaryOp::Mul => Value::Float(a * b),
+            BinaryOp::Div => Value::Float(a / b),
+            BinaryOp::Lt => Value::Bool(a < b),
+            BinaryOp::Le => Value::Bool(a <= b),
+            BinaryOp::Gt => Value::Bool(a > b),
+            BinaryOp::Ge => Value::Bool(a >= b),
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::And | BinaryOp::Or => unreachable!(),
+        }),
+        (op, Value::Str(a), Value::Str(b)) => match op {
+            BinaryOp::Add => Ok(Value::Str(a + &b)),
+            BinaryOp::Lt => Ok(Value::Bool(a < b)),
+            BinaryOp::Le => Ok(Value::Bool(a <= b)),
+            BinaryOp::Gt => Ok(Value::Bool(a > b)),
+            BinaryOp::Ge => Ok(Value::Bool(a >= b)),
+            op => error(format!("cannot apply `{}` to strings", op)),
+        },
+        (op, lhs, rhs) => error(format!("cannot apply `{}` to {} and {}", op, lhs, rhs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::run as eval;
+
+    #[test]
+    fn places_are_indexed_in_place() {
+        let source = "struct P { list<int> xs; map<string, int> m; }
+            fn main() -> list<int> {
+                P p = P { xs: [1, 2, 3], m: {\"a\": 1} };
+                p.xs[1] = 7;
+                p.m[\"b\"] = 2;
+                list<list<int>> g = [[4], [5, 6]];
+                return [p.xs[1], p.m[\"b\"], len(p.m), p.xs[0..2][1], g[1][1], len(g[1])];
+            }";
+        let expected = [7, 2, 2, 7, 6, 2].map(Value::Int).to_vec();
+        assert_eq!(eval(source).unwrap(), Value::List(expected));
+        assert!(eval("fn main() -> int { map<string, int> m = {}; return m[\"a\"]; }").is_err());
+    }
+
+    #[test]
+    fn stored_ints_become_floats() {
+        let source = "struct P { float x; }
+            fn half(float x) -> float { return x / 2; }
+            fn one() -> float { return 1; }
+            fn main() -> list<float> {
+                list<float> xs = [];
+                push(xs, 1);
+                insert(xs, 0, 3);
+                map<string, float> m = {};
+                m[\"a\"] = 5;
+                float y = 7;
+                P p = P { x: 9 };
+                p.x = 11;
+                return [xs[0] / 2, xs[1] / 2, m[\"a\"] / 2, y / 2, p.x / 2, half(13), one()];
+            }";
+        let expected = [1.5, 0.5, 2.5, 3.5, 5.5, 6.5, 1.0].map(Value::Float).to_vec();
+        assert_eq!(eval(source).unwrap(), Value::List(expected));
+        let source = "fn main() -> bool { list<float> xs = []; push(xs, 1); return contains(xs, 1); }";
+        assert_eq!(eval(source).unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn match_arms_take_the_type_of_the_first() {
+        let source = "fn main() -> float {
+                bool b = false;
+                list<float> xs = [match b { true => 1.5, false => 3 }];
+                return xs[0] / 2;
+            }";
+        assert_eq!(eval(source).unwrap(), Value::Float(1.5));
+    }
+
+    #[test]
+    fn mutator_arguments_run_left_to_right() {
+        let source = "fn main() -> list<int> {
+                list<list<int>> xs = [[], []];
+                list<int> order = [1, 0];
+                push(xs[pop(order)], pop(order));
+                return xs[0] + xs[1];
+            }";
+        assert_eq!(eval(source).unwrap(), Value::List(vec![Value::Int(1)]));
+    }
+
+    #[test]
+    fn deep_recursion_is_an_error() {
+        let source = "fn f(int n) -> int { if n == 0 { return 0; } return 1 + f(n - 1); }
+            fn main() -> int { return f(N); }";
+        assert_eq!(eval(&source.replace('N', "900")).unwrap(), Value::Int(900));
+        let err = eval(&source.replace('N', "100000")).unwrap_err();
+        assert!(err.message.starts_with("stack overflow"), "{}", err.message);
+    }
+
+    #[test]
+    fn method_receivers_are_evaluated_once() {
+        let source = "struct C { int n; fn bump(self) { self.n = self.n + 1; } }
+            fn main() -> list<int> {
+                list<C> cs = [C { n: 0 }];
+                list<int> indexes = [0, 0];
+                cs[pop(indexes)].bump();
+                return [cs[0].n, len(indexes)];
+            }";
+        assert_eq!(eval(source).unwrap(), Value::List(vec![Value::Int(1), Value::Int(1)]));
+    }
+
+    #[test]
+    fn and_and_or_stop_once_they_know_the_answer() {
+        let source = "fn main() -> list<bool> {
+                list<int> xs = [1, 2, 3, 4];
+                bool a = false && pop(xs) > 0;
+                bool b = true || pop(xs) > 0;
+                bool c = true && pop(xs) > 0;
+                bool d = false || pop(xs) > 0;
+                bool e = false && [1][5] == 1 || !true;
+                return [a, b, c, d, e, len(xs) == 2];
+            }";
+        let expected = [false, true, true, true, false, true].map(Value::Bool).to_vec();
+        assert_eq!(eval(source).unwrap(), Value::List(expected));
+    }
+
+    #[test]
+    fn break_and_continue_affect_the_innermost_loop() {
+        let source = "fn main() -> list<int> {
+                list<int> seen = [];
+                int i = 0;
+                while i < 4 {
+                    i = i + 1;
+                    if i == 2 { continue; }
+                    int j = 0;
+                    while true {
+                        j = j + 1;
+                        if j > i { break; }
+                        push(seen, i * 10 + j);
+                    }
+                    if i == 3 { break; }
+                }
+                return seen;
+            }";
+        assert_eq!(eval(source).unwrap(), Value::List([11, 31, 32, 33].map(Value::Int).to_vec()));
+    }
+
+    #[test]
+    fn changes_to_self_are_kept() {
+        let source = "struct C {
+                int n;
+                list<int> seen;
+                fn new(int n) -> C { return C { n: n, seen: [] }; }
+                fn bump(self) { self.n = self.n + 1; push(self.seen, self.n); }
+                fn twice(self) { self.bump(); self.bump(); }
+                fn get(self) -> int { return self.n; }
+            }
+            struct H { C c; }
+            fn main() -> list<int> {
+                C a = C.new(0);
+                a.bump();
+                a.twice();
+                H h = H { c: C.new(10) };
+                h.c.bump();
+                list<C> cs = [C.new(20)];
+                cs[0].twice();
+                return [a.n, len(a.seen), a.seen[2], h.c.n, cs[0].get(), C.new(30).get()];
+            }";
+        assert_eq!(eval(source).unwrap(), Value::List([3, 3, 3, 11, 22, 30].map(Value::Int).to_vec()));
+    }
+}
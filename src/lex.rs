@@ -1,27 +1,47 @@
-use std::default;
+use std::collections::VecDeque;
+use std::fmt;
 
-#[derive(Debug, PartialEq, Clone, Default, Eq, Hash)]
+use unicode_ident::{is_xid_continue, is_xid_start};
+
+use crate::diag::{self, Diagnostic};
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub enum Token {
-    Number(i32),
+    // an int literal, which can be one more than the largest int so that the smallest can be written
+    // as `-9223372036854775808`. the parser rejects it anywhere else
+    Number(u64),
+    FloatLiteral(f64),
+    StringLiteral(String),
+    // `/// text`, holding the text after the slashes
+    DocComment(String),
     If,
     Else,
     Elif,
     While,
+    Break,
+    Continue,
+    For,
+    In,
     Fn,
     Return,
-    Arguments,
-    Fields,
-    Functions,
     Struct,
+    Enum,
+    Match,
+    // the receiver of a method
+    SelfValue,
     Int,
     String,
     Float,
+    Bool,
     List,
+    Map,
+    True,
+    False,
     Comma,
     Colon,
     DecimalPoint,
-
-    #[default]
+    // the `null` keyword, naming both the type and its only value
     Null,
     Identifier(String),
     Plus,
@@ -45,21 +65,204 @@ pub enum Token {
     LessThanOrEqual,
     GreaterThanOrEqual,
     Arrow,
+    FatArrow,
+    DoubleAmpersand,
+    DoublePipe,
+    DotDot,
+    DotDotEqual,
 
-    // structure tokens
-    Program,
-    Block,
+    #[default]
     EOF,
-    Line,
 
     SemiColon,
 }
 
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Token::Number(n) => return write!(f, "{}", n),
+            Token::FloatLiteral(x) => return write!(f, "{:?}", x),
+            Token::Identifier(name) => return write!(f, "{}", name),
+            Token::StringLiteral(value) => return write!(f, "{:?}", value),
+            Token::DocComment(text) => return write!(f, "/// {}", text),
+            Token::If => "if",
+            Token::Else => "else",
+            Token::Elif => "elif",
+            Token::While => "while",
+            Token::Break => "break",
+            Token::Continue => "continue",
+            Token::For => "for",
+            Token::In => "in",
+            Token::Fn => "fn",
+            Token::Return => "return",
+            Token::Struct => "struct",
+            Token::Enum => "enum",
+            Token::Match => "match",
+            Token::SelfValue => "self",
+            Token::Int => "int",
+            Token::String => "string",
+            Token::Float => "float",
+            Token::Bool => "bool",
+            Token::List => "list",
+            Token::Map => "map",
+            Token::True => "true",
+            Token::False => "false",
+            Token::Comma => ",",
+            Token::Colon => ":",
+            Token::DecimalPoint => ".",
+            Token::Null => "null",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Equal => "=",
+            Token::Bang => "!",
+            Token::LessThan => "<",
+            Token::GreaterThan => ">",
+            Token::RightParen => ")",
+            Token::LeftParen => "(",
+            Token::RightSquareBracket => "]",
+            Token::LeftSquareBracket => "[",
+            Token::RightCurlyBracket => "}",
+            Token::LeftCurlyBracket => "{",
+            Token::DoubleEqual => "==",
+            Token::NotEqual => "!=",
+            Token::LessThanOrEqual => "<=",
+            Token::GreaterThanOrEqual => ">=",
+            Token::Arrow => "->",
+            Token::FatArrow => "=>",
+            Token::DoubleAmpersand => "&&",
+            Token::DoublePipe => "||",
+            Token::DotDot => "..",
+            Token::DotDotEqual => "..=",
+            Token::EOF => "end of file",
+            Token::SemiColon => ";",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// a region of the source: `offset` and `len` are in bytes, `line` and `column` start at 1
+#[derive(Debug, PartialEq, Clone, Copy, Default, Eq, Hash)]
+pub struct Span {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+}
+
+impl Span {
+    // the span from the start of `self` to the end of `end`
+    pub fn to(self, end: Span) -> Span {
+        Span {
+            len: (end.offset + end.len).saturating_sub(self.offset),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+// the lexer walks the input once with a byte cursor: `ch` is the character starting at byte
+// `position`, and `read_position` is the byte just after it. at the end of the input `ch` is '\0'
+// and both positions stay at the input's length
 pub struct Lexer {
     input: String,
     position: usize,
     read_position: usize,
     ch: char,
+
+    // line and column of the input at byte `scanned`, for spans
+    line: usize,
+    column: usize,
+    scanned: usize,
+
+    // what `next` returns before lexing more: errors found so far and the token they came with
+    queue: VecDeque<Result<SpannedToken, LexError>>,
+    done: bool,
+}
+
+// a problem with the characters of the input. the lexer carries on after each one, so every
+// problem in a file is reported at once
+#[derive(Debug, PartialEq, Clone)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexErrorKind {
+    UnknownCharacter(char),
+    UnterminatedString,
+    UnterminatedComment,
+    // a backslash followed by anything but n, t, ", \ or u
+    UnknownEscape(char),
+    MissingUnicodeBrace,
+    UnterminatedUnicodeEscape,
+    InvalidUnicodeEscape,
+    IntegerTooLarge,
+    FloatTooLarge,
+    // a `0x`, `0o` or `0b` literal that is empty or has a digit its base doesn't allow, with the
+    // name of the base
+    MissingDigits(&'static str),
+    InvalidDigit(char, &'static str),
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            LexErrorKind::UnknownCharacter(c) => write!(f, "unknown character `{}`", c),
+            LexErrorKind::UnterminatedString => write!(f, "unterminated string"),
+            LexErrorKind::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexErrorKind::UnknownEscape(c) => write!(f, "unknown escape `\\{}`", c),
+            LexErrorKind::MissingUnicodeBrace => write!(f, "missing `{{` in unicode escape"),
+            LexErrorKind::UnterminatedUnicodeEscape => write!(f, "unterminated unicode escape"),
+            LexErrorKind::InvalidUnicodeEscape => write!(f, "invalid unicode escape"),
+            LexErrorKind::IntegerTooLarge => write!(f, "integer literal is too large"),
+            LexErrorKind::FloatTooLarge => write!(f, "float literal is too large"),
+            LexErrorKind::MissingDigits(base) => write!(f, "{} literal has no digits", base),
+            LexErrorKind::InvalidDigit(c, base) => write!(f, "invalid digit `{}` in {} literal", c, base),
+        }
+    }
+}
+
+impl LexError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let (code, label) = match self.kind {
+            LexErrorKind::UnknownCharacter(_) => (diag::UNKNOWN_CHARACTER, "not valid in a simpl program".to_string()),
+            LexErrorKind::UnterminatedString => (diag::UNTERMINATED_STRING, "string starts here".to_string()),
+            LexErrorKind::UnterminatedComment => (diag::UNTERMINATED_COMMENT, "comment starts here".to_string()),
+            LexErrorKind::UnknownEscape(_) => (
+                diag::INVALID_ESCAPE,
+                "strings can use \\n, \\t, \\\", \\\\ and \\u{...}".to_string(),
+            ),
+            LexErrorKind::MissingUnicodeBrace => (diag::INVALID_ESCAPE, "expected `\\u{...}`".to_string()),
+            LexErrorKind::UnterminatedUnicodeEscape => {
+                (diag::INVALID_ESCAPE, "expected `}` after the hex digits".to_string())
+            }
+            LexErrorKind::InvalidUnicodeEscape => (diag::INVALID_ESCAPE, "not a unicode character".to_string()),
+            LexErrorKind::IntegerTooLarge => (
+                diag::LITERAL_OUT_OF_RANGE,
+                format!("larger than the largest int, {}", i64::MAX),
+            ),
+            LexErrorKind::FloatTooLarge => (diag::LITERAL_OUT_OF_RANGE, "larger than the largest float".to_string()),
+            LexErrorKind::MissingDigits(_) => (diag::INVALID_LITERAL, "expected digits after the prefix".to_string()),
+            LexErrorKind::InvalidDigit(..) => (diag::INVALID_LITERAL, "not a valid digit for this base".to_string()),
+        };
+        Diagnostic::error(self.to_string())
+            .with_code(code)
+            .with_label(self.span, label)
+    }
 }
 
 impl Lexer {
@@ -69,14 +272,47 @@ impl Lexer {
             position: 0,
             read_position: 0,
             ch: '\0',
+            line: 1,
+            column: 1,
+            scanned: 0,
+            queue: VecDeque::new(),
+            done: false,
         };
         l.read_char();
         l
     }
 
-    pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+    // report a problem at `offset..offset + len`, which must not come before the last span taken
+    fn error(&mut self, kind: LexErrorKind, offset: usize, len: usize) {
+        let span = self.span_at(offset, len);
+        self.queue.push_back(Err(LexError { kind, span }));
+    }
 
+    // spans are taken in order, so counting lines and columns on from the last one keeps this linear.
+    // columns count characters rather than bytes
+    fn span_at(&mut self, offset: usize, len: usize) -> Span {
+        if let Some(skipped) = self.input.get(self.scanned..offset) {
+            for c in skipped.chars() {
+                if c == '\n' {
+                    self.line += 1;
+                    self.column = 1;
+                } else {
+                    self.column += 1;
+                }
+            }
+            self.scanned = offset;
+        }
+
+        Span {
+            offset,
+            line: self.line,
+            column: self.column,
+            len,
+        }
+    }
+
+    // the token starting at `ch`, or `None` for a character that can't start one
+    fn read_token(&mut self) -> Option<Token> {
         let tok = match self.ch {
             '(' => Token::LeftParen,
             ')' => Token::RightParen,
@@ -97,11 +333,16 @@ impl Lexer {
             
             },
             '*' => Token::Star,
+            // ordinary comments have been skipped already, so `//` here starts a doc comment
+            '/' if self.peek_char() == '/' => self.read_doc_comment(),
             '/' => Token::Slash,
             '=' => {
                 if self.peek_char() == '=' {
                     self.read_char();
                     Token::DoubleEqual
+                } else if self.peek_char() == '>' {
+                    self.read_char();
+                    Token::FatArrow
                 } else {
                     Token::Equal
                 }
@@ -130,78 +371,254 @@ impl Lexer {
                     Token::GreaterThan
                 }
             }
+            // a single `&` or `|` isn't an operator, so it is an unknown character
+            '&' if self.peek_char() == '&' => {
+                self.read_char();
+                Token::DoubleAmpersand
+            }
+            '|' if self.peek_char() == '|' => {
+                self.read_char();
+                Token::DoublePipe
+            }
+            '"' => self.read_string(),
             ',' => Token::Comma,
+            '.' if self.peek_char() == '.' => {
+                self.read_char();
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    Token::DotDotEqual
+                } else {
+                    Token::DotDot
+                }
+            }
             '.' => Token::DecimalPoint,
-            '\0' => Token::EOF,
+            '\0' if self.at_eof() => Token::EOF,
             _ => {
-                if self.ch.is_alphabetic() {
+                if is_xid_start(self.ch) || self.ch == '_' {
                     let ident = self.read_identifier();
                     match ident.as_str() {
                         "if" => Token::If,
                         "else" => Token::Else,
                         "elif" => Token::Elif,
                         "while" => Token::While,
+                        "break" => Token::Break,
+                        "continue" => Token::Continue,
+                        "for" => Token::For,
+                        "in" => Token::In,
                         "fn" => Token::Fn,
                         "return" => Token::Return,
                         "int" => Token::Int,
                         "string" => Token::String,
                         "float" => Token::Float,
+                        "bool" => Token::Bool,
                         "list" => Token::List,
+                        "map" => Token::Map,
+                        "true" => Token::True,
+                        "false" => Token::False,
                         "struct" => Token::Struct,
+                        "enum" => Token::Enum,
+                        "match" => Token::Match,
+                        "self" => Token::SelfValue,
+                        "null" => Token::Null,
                         _ => Token::Identifier(ident),
                     }
-                } else if self.ch.is_digit(10) {
-                    Token::Number(self.read_number())
+                } else if self.ch.is_ascii_digit() {
+                    self.read_number()
                 } else {
-                    Token::Null
+                    // skipped, so lexing carries on after it
+                    self.read_char();
+                    return None;
                 }
             }
         };
         self.read_char();
-        tok
+        Some(tok)
     }
 
     pub fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.ch = '\0';
-        } else {
-            self.ch = self.input.chars().nth(self.read_position).unwrap();
-        }
         self.position = self.read_position;
-        self.read_position += 1;
+        match self.input[self.read_position..].chars().next() {
+            Some(c) => {
+                self.ch = c;
+                self.read_position += c.len_utf8();
+            }
+            None => self.ch = '\0',
+        }
     }
 
     pub fn peek_char(&self) -> char {
-        if self.read_position >= self.input.len() {
-            '\0'
-        } else {
-            self.input.chars().nth(self.read_position).unwrap()
-        }
+        self.peek_nth_char(0)
     }
 
+    // the character `n` places after the one `peek_char` returns
+    fn peek_nth_char(&self, n: usize) -> char {
+        self.input[self.read_position..].chars().nth(n).unwrap_or('\0')
+    }
+
+    // '\0' is also a character the input could contain, so the end is found by position
+    fn at_eof(&self) -> bool {
+        self.position >= self.input.len()
+    }
+
+    // a unicode identifier: a XID_Start character or `_`, followed by any number of XID_Continue
+    // characters, which include digits and `_`. `ch` is left on the last character of the name
     pub fn read_identifier(&mut self) -> String {
         let position = self.position;
-        while self.ch.is_alphabetic() || self.ch == '_' {
+        while is_xid_continue(self.peek_char()) {
             self.read_char();
         }
+        self.input[position..self.read_position].to_string()
+    }
 
+    // an int, or a float if it has a fraction or an exponent: `1`, `1.5`, `1e-3`, `2.5E10`.
+    // the `.` must be followed by a digit, so `1.foo` is still field access. digits can be grouped
+    // with underscores, as in `1_000_000`. `ch` is left on the last character of the number
+    pub fn read_number(&mut self) -> Token {
+        if self.ch == '0' && matches!(self.peek_char(), 'x' | 'o' | 'b') {
+            return self.read_radix_number();
+        }
+
+        let position = self.position;
+        let mut float = false;
+        self.skip_digits();
+
+        if self.peek_char() == '.' && self.peek_nth_char(1).is_ascii_digit() {
+            float = true;
+            self.read_char();
+            self.skip_digits();
+        }
 
-        self.read_position -= 1;
-        self.ch = self.input.chars().nth(self.read_position).unwrap();
+        if matches!(self.peek_char(), 'e' | 'E') {
+            let digits = if matches!(self.peek_nth_char(1), '+' | '-') { 2 } else { 1 };
+            if self.peek_nth_char(digits).is_ascii_digit() {
+                float = true;
+                for _ in 0..digits {
+                    self.read_char();
+                }
+                self.skip_digits();
+            }
+        }
 
-        self.input[position..self.position].to_string()
+        let len = self.read_position - position;
+        let text = self.input[position..position + len].replace('_', "");
+        if float {
+            let value: f64 = text.parse().unwrap();
+            if value.is_infinite() {
+                self.error(LexErrorKind::FloatTooLarge, position, len);
+            }
+            Token::FloatLiteral(value)
+        } else {
+            self.int_literal(text.parse().ok(), position, len)
+        }
     }
 
-    pub fn read_number(&mut self) -> i32 {
+    // `0x1F`, `0o17` or `0b1010`. `ch` is on the leading `0`
+    fn read_radix_number(&mut self) -> Token {
         let position = self.position;
-        while self.ch.is_digit(10) {
+        self.read_char();
+        let (radix, base) = match self.ch {
+            'x' => (16, "hexadecimal"),
+            'o' => (8, "octal"),
+            _ => (2, "binary"),
+        };
+
+        // letters and digits that don't belong are taken into the literal, so `0b102` is one bad
+        // literal rather than `0b10` followed by `2`
+        while self.peek_char().is_ascii_alphanumeric() || self.peek_char() == '_' {
             self.read_char();
         }
 
-        self.read_position -= 1;
-        self.ch = self.input.chars().nth(self.read_position).unwrap();
+        let len = self.read_position - position;
+        let digits = self.input[position + 2..position + len].replace('_', "");
+        if digits.is_empty() {
+            self.error(LexErrorKind::MissingDigits(base), position, len);
+            return Token::Number(0);
+        }
+        if let Some(bad) = digits.chars().find(|c| !c.is_digit(radix)) {
+            self.error(LexErrorKind::InvalidDigit(bad, base), position, len);
+            return Token::Number(0);
+        }
 
-        self.input[position..self.position].parse().unwrap()
+        let value = u64::from_str_radix(&digits, radix).ok();
+        self.int_literal(value, position, len)
+    }
+
+    // an int literal's value, or `None` if it doesn't fit in an int even when negated
+    fn int_literal(&mut self, value: Option<u64>, position: usize, len: usize) -> Token {
+        match value {
+            Some(n) if n <= i64::MIN.unsigned_abs() => Token::Number(n),
+            _ => {
+                self.error(LexErrorKind::IntegerTooLarge, position, len);
+                Token::Number(0)
+            }
+        }
+    }
+
+    fn skip_digits(&mut self) {
+        while self.peek_char().is_ascii_digit() || self.peek_char() == '_' {
+            self.read_char();
+        }
+    }
+
+    // a double-quoted string, leaving `ch` on the closing quote
+    fn read_string(&mut self) -> Token {
+        let start = self.position;
+        let mut value = String::new();
+        loop {
+            self.read_char();
+            match self.ch {
+                '"' => break,
+                '\0' if self.at_eof() => {
+                    self.error(LexErrorKind::UnterminatedString, start, 1);
+                    break;
+                }
+                '\\' => {
+                    let escape = self.position;
+                    self.read_char();
+                    match self.ch {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        '"' => value.push('"'),
+                        '\\' => value.push('\\'),
+                        'u' => value.extend(self.read_unicode_escape(escape)),
+                        // the end of the input is reported as an unterminated string on the next loop
+                        '\0' if self.at_eof() => {}
+                        other => self.error(LexErrorKind::UnknownEscape(other), escape, 1 + other.len_utf8()),
+                    }
+                }
+                c => value.push(c),
+            }
+        }
+        Token::StringLiteral(value)
+    }
+
+    // \u{...} with one to six hex digits naming a unicode scalar value. `ch` is on the `u`
+    fn read_unicode_escape(&mut self, escape: usize) -> Option<char> {
+        if self.peek_char() != '{' {
+            self.error(LexErrorKind::MissingUnicodeBrace, escape, 2);
+            return None;
+        }
+        self.read_char();
+
+        let mut digits = String::new();
+        while self.peek_char().is_ascii_hexdigit() {
+            self.read_char();
+            digits.push(self.ch);
+        }
+        if self.peek_char() != '}' {
+            self.error(LexErrorKind::UnterminatedUnicodeEscape, escape, self.read_position - escape);
+            return None;
+        }
+        self.read_char();
+
+        let ch = u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| digits.len() <= 6)
+            .and_then(char::from_u32);
+        if ch.is_none() {
+            self.error(LexErrorKind::InvalidUnicodeEscape, escape, self.read_position - escape);
+        }
+        ch
     }
 
     pub fn skip_whitespace(&mut self) {
@@ -209,4 +626,274 @@ impl Lexer {
             self.read_char();
         }
     }
-}
\ No newline at end of file
+
+    // whitespace and comments, stopping at a `///` doc comment, which is a token.
+    // `////` and longer are ordinary comments, as they are usually used for separators
+    fn skip_trivia(&mut self) {
+        loop {
+            self.skip_whitespace();
+            match (self.ch, self.peek_char()) {
+                ('/', '/') if self.peek_nth_char(1) == '/' && self.peek_nth_char(2) != '/' => return,
+                ('/', '/') => {
+                    while self.ch != '\n' && !self.at_eof() {
+                        self.read_char();
+                    }
+                }
+                ('/', '*') => self.skip_block_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    // a `/* */` comment, which can contain other block comments
+    fn skip_block_comment(&mut self) {
+        let start = self.position;
+        let mut depth = 0;
+        loop {
+            match (self.ch, self.peek_char()) {
+                ('/', '*') => {
+                    self.read_char();
+                    depth += 1;
+                }
+                ('*', '/') => {
+                    self.read_char();
+                    depth -= 1;
+                }
+                ('\0', _) if self.at_eof() => {
+                    self.error(LexErrorKind::UnterminatedComment, start, 2);
+                    return;
+                }
+                _ => {}
+            }
+            self.read_char();
+            if depth == 0 {
+                return;
+            }
+        }
+    }
+
+    // the rest of the line after `///`, without the space that usually follows the slashes.
+    // `ch` is left on the last character of the line
+    fn read_doc_comment(&mut self) -> Token {
+        self.read_char();
+        self.read_char();
+        let mut text = String::new();
+        while self.peek_char() != '\n' && self.read_position < self.input.len() {
+            self.read_char();
+            text.push(self.ch);
+        }
+        let text = text.strip_prefix(' ').unwrap_or(&text).trim_end();
+        Token::DocComment(text.to_string())
+    }
+}
+
+impl Iterator for Lexer {
+    type Item = Result<SpannedToken, LexError>;
+
+    // every token in the input ending with `EOF`, with any errors among them in source order
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.queue.is_empty() && !self.done {
+            self.skip_trivia();
+
+            // the token's span is taken first, so errors found inside it come after it in order
+            let start = self.position;
+            let mut span = self.span_at(start, 0);
+            let token = self.read_token();
+            span.len = self.position - start;
+
+            match token {
+                None => {
+                    let ch = self.input[start..].chars().next().unwrap_or_default();
+                    self.queue.push_back(Err(LexError {
+                        kind: LexErrorKind::UnknownCharacter(ch),
+                        span,
+                    }));
+                }
+                Some(token) => {
+                    self.done = token == Token::EOF;
+                    self.queue.push_back(Ok(SpannedToken { token, span }));
+                }
+            }
+        }
+        self.queue.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::tokens;
+
+    // the tokens of `source`, leaving out the `EOF` at the end
+    fn lex(source: &str) -> Vec<Token> {
+        let mut tokens: Vec<Token> = tokens(source).into_iter().map(|tok| tok.token).collect();
+        assert_eq!(tokens.pop(), Some(Token::EOF));
+        tokens
+    }
+
+    // the problems lexing `source` finds
+    fn errors(source: &str) -> Vec<LexErrorKind> {
+        Lexer::new(source.to_string()).filter_map(|result| result.err()).map(|err| err.kind).collect()
+    }
+
+    fn string(value: &str) -> Token {
+        Token::StringLiteral(value.to_string())
+    }
+
+    #[test]
+    fn strings_can_hold_escapes() {
+        assert_eq!(lex(r#""a\nb\tc\"d\\e""#), vec![string("a\nb\tc\"d\\e")]);
+        assert_eq!(lex(r#""\u{41}\u{e9}\u{1F600}""#), vec![string("Aé😀")]);
+        assert_eq!(lex(r#""" "x""#), vec![string(""), string("x")]);
+    }
+
+    #[test]
+    fn bad_escapes_are_reported_and_left_out() {
+        use LexErrorKind::*;
+        assert_eq!(errors(r#""\q""#), vec![UnknownEscape('q')]);
+        assert_eq!(errors(r#""\u41""#), vec![MissingUnicodeBrace]);
+        assert_eq!(errors(r#""\u{41""#), vec![UnterminatedUnicodeEscape]);
+        assert_eq!(errors(r#""\u{110000}" "\u{D800}" "\u{0000041}""#), vec![InvalidUnicodeEscape; 3]);
+        assert_eq!(errors("\"abc"), vec![UnterminatedString]);
+
+        let lexed: Vec<_> = Lexer::new(r#"x = "a\qb";"#.to_string()).collect();
+        assert_eq!(lexed.iter().filter(|result| result.is_err()).count(), 1);
+        assert!(lexed.contains(&Ok(SpannedToken {
+            token: string("ab"),
+            span: Span { offset: 4, line: 1, column: 5, len: 6 },
+        })));
+    }
+
+    #[test]
+    fn floats_have_a_fraction_or_an_exponent() {
+        use Token::*;
+        assert_eq!(lex("1.5 0.25 1_000.5"), vec![FloatLiteral(1.5), FloatLiteral(0.25), FloatLiteral(1000.5)]);
+        let exponents = vec![FloatLiteral(1e-3), FloatLiteral(2.5e10), FloatLiteral(100.0), FloatLiteral(3.0)];
+        assert_eq!(lex("1e-3 2.5E10 1e+2 3e0"), exponents);
+        assert_eq!(lex("1.foo"), vec![Number(1), DecimalPoint, Identifier("foo".to_string())]);
+        assert_eq!(lex("1.e5"), vec![Number(1), DecimalPoint, Identifier("e5".to_string())]);
+        assert_eq!(lex("1..3"), vec![Number(1), DotDot, Number(3)]);
+        assert_eq!(lex("2e"), vec![Number(2), Identifier("e".to_string())]);
+        assert_eq!(lex("2e-x"), vec![Number(2), Identifier("e".to_string()), Minus, Identifier("x".to_string())]);
+        assert_eq!(errors("1e999 1.5"), vec![LexErrorKind::FloatTooLarge]);
+    }
+
+    #[test]
+    fn ints_can_be_written_in_other_bases() {
+        use Token::Number;
+        assert_eq!(lex("0xFF 0x1f 0o17 0b1010 0"), vec![Number(255), Number(31), Number(15), Number(10), Number(0)]);
+        assert_eq!(lex("1_000_000 0xFF_FF 0b_1"), vec![Number(1_000_000), Number(0xFFFF), Number(1)]);
+        assert_eq!(lex("0x7FFF_FFFF_FFFF_FFFF"), vec![Number(i64::MAX as u64)]);
+    }
+
+    #[test]
+    fn bad_ints_are_reported() {
+        use LexErrorKind::*;
+        assert_eq!(errors("0x 0b_"), vec![MissingDigits("hexadecimal"), MissingDigits("binary")]);
+        assert_eq!(errors("0b102 0o8 0xFG"), vec![
+            InvalidDigit('2', "binary"),
+            InvalidDigit('8', "octal"),
+            InvalidDigit('G', "hexadecimal"),
+        ]);
+
+        // one more than the largest int is let through for the parser to negate
+        assert_eq!(lex("9223372036854775808"), vec![Token::Number(1 << 63)]);
+        assert_eq!(errors("9223372036854775809 0x8000_0000_0000_0001"), vec![IntegerTooLarge; 2]);
+        assert_eq!(errors("99999999999999999999999"), vec![IntegerTooLarge]);
+
+        // the whole literal is reported, and lexing goes on after it
+        let lexed: Vec<_> = Lexer::new("0b102 + 1".to_string()).collect();
+        assert_eq!(lexed[0].as_ref().unwrap_err().span, Span { offset: 0, line: 1, column: 1, len: 5 });
+        assert_eq!(lexed[2].as_ref().unwrap().token, Token::Plus);
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let ab = vec![Token::Identifier("a".to_string()), Token::Identifier("b".to_string())];
+        assert_eq!(lex("a // c\nb // d"), ab);
+        assert_eq!(lex("a /* c */ b"), ab);
+        assert_eq!(lex("a /* c /* d */ e */ b /**/"), ab);
+        assert_eq!(lex("a /* c\n// d */\nb"), ab);
+        assert_eq!(lex("a //// c\nb"), ab);
+        assert_eq!(lex("a /*/ b */ / b"), vec![Token::Identifier("a".to_string()), Token::Slash, ab[1].clone()]);
+        assert_eq!(errors("a /* c /* d */"), vec![LexErrorKind::UnterminatedComment]);
+    }
+
+    #[test]
+    fn three_slashes_start_a_doc_comment() {
+        let doc = |text: &str| Token::DocComment(text.to_string());
+        assert_eq!(lex("/// adds one\nfn"), vec![doc("adds one"), Token::Fn]);
+        assert_eq!(lex("///  indented  \n///\n///x"), vec![doc(" indented"), doc(""), doc("x")]);
+        assert_eq!(lex("//// separator\n/////\nfn"), vec![Token::Fn]);
+        assert_eq!(lex("/// é"), vec![doc("é")]);
+    }
+
+    #[test]
+    fn identifiers_follow_the_unicode_rules() {
+        let names = ["café", "naïve", "Δx", "変数", "x·y"];
+        let idents: Vec<_> = names.iter().map(|name| Token::Identifier(name.to_string())).collect();
+        assert_eq!(lex(&names.join(" ")), idents);
+        assert_eq!(errors("😀 a"), vec![LexErrorKind::UnknownCharacter('😀')]);
+    }
+
+    #[test]
+    fn spans_count_bytes_but_columns_count_characters() {
+        let spans: Vec<_> = tokens("é = \"ü\";\n  ß").into_iter().map(|tok| tok.span).collect();
+        let span = |offset, line, column, len| Span { offset, line, column, len };
+        assert_eq!(spans, vec![
+            span(0, 1, 1, 2),
+            span(3, 1, 3, 1),
+            span(5, 1, 5, 4),
+            span(9, 1, 8, 1),
+            span(13, 2, 3, 2),
+            span(15, 2, 4, 0),
+        ]);
+
+        let lexed: Vec<_> = Lexer::new("ü ü @".to_string()).collect();
+        assert_eq!(lexed[2].as_ref().unwrap_err().span, span(6, 1, 5, 1));
+    }
+
+    #[test]
+    fn identifiers_can_hold_digits_and_underscores() {
+        let ident = |name: &str| Token::Identifier(name.to_string());
+        assert_eq!(lex("x1 _x _ a_b2 __"), vec![ident("x1"), ident("_x"), ident("_"), ident("a_b2"), ident("__")]);
+        assert_eq!(lex("1x"), vec![Token::Number(1), ident("x")]);
+        assert_eq!(lex("iff self_ nulls fn2"), vec![ident("iff"), ident("self_"), ident("nulls"), ident("fn2")]);
+    }
+
+    #[test]
+    fn tokens_and_errors_come_in_order() {
+        let mut lexer = Lexer::new("a @ b \"\\q\" #".to_string());
+        let mut next = || lexer.next().map(|result| result.map(|tok| tok.token).map_err(|err| err.kind));
+        assert_eq!(next(), Some(Ok(Token::Identifier("a".to_string()))));
+        assert_eq!(next(), Some(Err(LexErrorKind::UnknownCharacter('@'))));
+        assert_eq!(next(), Some(Ok(Token::Identifier("b".to_string()))));
+        // a problem inside a token is reported before the token it is in
+        assert_eq!(next(), Some(Err(LexErrorKind::UnknownEscape('q'))));
+        assert_eq!(next(), Some(Ok(Token::StringLiteral(String::new()))));
+        assert_eq!(next(), Some(Err(LexErrorKind::UnknownCharacter('#'))));
+        assert_eq!(next(), Some(Ok(Token::EOF)));
+        assert_eq!(next(), None);
+        assert_eq!(next(), None);
+    }
+
+    #[test]
+    fn every_unknown_character_is_reported() {
+        let unknown: Vec<_> = "@#%&|$?\\\0".chars().map(LexErrorKind::UnknownCharacter).collect();
+        assert_eq!(errors("a @ # % & | $ ? \\ \0 b"), unknown);
+
+        // the tokens around them are kept
+        let lexed: Vec<_> = Lexer::new("a & b".to_string()).filter_map(Result::ok).map(|tok| tok.token).collect();
+        assert_eq!(lexed, vec![Token::Identifier("a".to_string()), Token::Identifier("b".to_string()), Token::EOF]);
+        assert_eq!(lex("&& ||"), vec![Token::DoubleAmpersand, Token::DoublePipe]);
+
+        let err = Lexer::new("@".to_string()).next().unwrap().unwrap_err();
+        assert_eq!(err.to_diagnostic().code, Some(diag::UNKNOWN_CHARACTER));
+    }
+
+    #[test]
+    fn null_is_a_keyword() {
+        assert_eq!(lex("null"), vec![Token::Null]);
+        assert_eq!(lex("Null"), vec![Token::Identifier("Null".to_string())]);
+    }
+}
@@ -0,0 +1,15 @@
+// the simpl language: lexing, parsing, checking and interpreting programs. the `simpl` binary
+// drives these passes from the command line
+
+pub mod ast;
+pub mod diag;
+pub mod interp;
+pub mod lex;
+pub mod lower;
+pub mod parse;
+pub mod resolve;
+pub mod typeck;
+pub mod validate;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,251 @@
+// desugaring that runs once a program has been checked, so the interpreter only has to handle the
+// core statements. `for` loops become `while` loops over hidden locals, whose names can't clash with
+// the program's since `#` can't appear in an identifier
+
+use crate::ast::*;
+use crate::lex::Span;
+
+const NEXT: &str = "for#next";
+const END: &str = "for#end";
+const MORE: &str = "for#more";
+const LIST: &str = "for#list";
+const INDEX: &str = "for#index";
+
+pub fn lower(mut program: Program) -> Program {
+    for item in &mut program.items {
+        match item {
+            Item::Fn(function) => lower_block(&mut function.body),
+            Item::Struct(decl) => {
+                for method in &mut decl.methods {
+                    lower_block(&mut method.body);
+                }
+            }
+            Item::Enum(_) => {}
+        }
+    }
+    program
+}
+
+fn lower_block(block: &mut Block) {
+    for stmt in &mut block.stmts {
+        match &mut stmt.kind {
+            StmtKind::If {
+                branches,
+                otherwise,
+            } => {
+                for (_, body) in branches {
+                    lower_block(body);
+                }
+                if let Some(body) = otherwise {
+                    lower_block(body);
+                }
+            }
+            StmtKind::While { body, .. } | StmtKind::Block(body) => lower_block(body),
+            StmtKind::Match { arms, .. } => {
+                for arm in arms {
+                    if let ArmBody::Block(body) = &mut arm.body {
+                        lower_block(body);
+                    }
+                }
+            }
+            StmtKind::For { var, iter, body } => {
+                lower_block(body);
+                stmt.kind = lower_for(var.clone(), iter.clone(), body.clone(), stmt.span);
+            }
+            _ => {}
+        }
+    }
+}
+
+// for i in start..end { body } becomes
+//
+//     int for#next = start;
+//     int for#end = end;
+//     while for#next < for#end {
+//         int i = for#next;
+//         for#next = for#next + 1;
+//         { body }
+//     }
+//
+// the variable moves on before the body runs, so `continue` can't skip it. an inclusive range
+// keeps a flag instead of comparing, so that `..=` can end at the largest int without overflowing.
+// lists are walked by index in the same way, over a copy taken before the loop starts and up to
+// its length, which is also taken once
+fn lower_for(var: Ident, iter: Expr, body: Block, span: Span) -> StmtKind {
+    let at = iter.span;
+    let mut stmts = Vec::new();
+    let mut head = Vec::new();
+
+    let cond = match iter.kind {
+        ExprKind::Range {
+            start,
+            end,
+            inclusive: false,
+        } => {
+            stmts.push(bind(Some(Type::Int), ident(NEXT, at), *start));
+            stmts.push(bind(Some(Type::Int), ident(END, at), *end));
+            head.push(bind(Some(Type::Int), var, name(NEXT, at)));
+            head.push(increment(NEXT, at));
+            binary(BinaryOp::Lt, name(NEXT, at), name(END, at))
+        }
+        ExprKind::Range {
+            start,
+            end,
+            inclusive: true,
+        } => {
+            stmts.push(bind(Some(Type::Int), ident(NEXT, at), *start));
+            stmts.push(bind(Some(Type::Int), ident(END, at), *end));
+            let more = binary(BinaryOp::Le, name(NEXT, at), name(END, at));
+            stmts.push(bind(Some(Type::Bool), ident(MORE, at), more));
+            head.push(bind(Some(Type::Int), var, name(NEXT, at)));
+            head.push(assign(MORE, binary(BinaryOp::Lt, name(NEXT, at), name(END, at))));
+            let step = Block {
+                stmts: vec![increment(NEXT, at)],
+                span: at,
+            };
+            let step = StmtKind::If {
+                branches: vec![(name(MORE, at), step)],
+                otherwise: None,
+            };
+            head.push(Stmt { kind: step, span: at });
+            name(MORE, at)
+        }
+        _ => {
+            stmts.push(bind(None, ident(LIST, at), iter));
+            stmts.push(bind(Some(Type::Int), ident(INDEX, at), expr(ExprKind::Int(0), at)));
+            let item = ExprKind::Index {
+                expr: Box::new(name(LIST, at)),
+                index: Box::new(name(INDEX, at)),
+            };
+            let len = ExprKind::Call {
+                callee: Box::new(name("len", at)),
+                args: vec![name(LIST, at)],
+            };
+            stmts.push(bind(Some(Type::Int), ident(END, at), expr(len, at)));
+            head.push(bind(None, var, expr(item, at)));
+            head.push(increment(INDEX, at));
+            binary(BinaryOp::Lt, name(INDEX, at), name(END, at))
+        }
+    };
+
+    let body_span = body.span;
+    head.push(Stmt {
+        kind: StmtKind::Block(body),
+        span: body_span,
+    });
+    stmts.push(Stmt {
+        kind: StmtKind::While {
+            cond,
+            body: Block {
+                stmts: head,
+                span: body_span,
+            },
+        },
+        span,
+    });
+    StmtKind::Block(Block { stmts, span })
+}
+
+fn ident(name: &str, span: Span) -> Ident {
+    Ident {
+        name: name.to_string(),
+        span,
+    }
+}
+
+fn expr(kind: ExprKind, span: Span) -> Expr {
+    Expr { kind, span }
+}
+
+fn name(name: &str, span: Span) -> Expr {
+    expr(ExprKind::Ident(name.to_string()), span)
+}
+
+fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    let span = lhs.span;
+    let kind = ExprKind::Binary {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    };
+    expr(kind, span)
+}
+
+fn bind(ty: Option<Type>, name: Ident, value: Expr) -> Stmt {
+    let span = name.span;
+    let kind = StmtKind::Let {
+        ty: ty.map(|kind| TypeExpr { kind, span }),
+        name,
+        value: Some(value),
+    };
+    Stmt { kind, span }
+}
+
+fn assign(target: &str, value: Expr) -> Stmt {
+    let span = value.span;
+    let kind = StmtKind::Assign {
+        target: name(target, span),
+        value,
+    };
+    Stmt { kind, span }
+}
+
+fn increment(target: &str, span: Span) -> Stmt {
+    assign(target, binary(BinaryOp::Add, name(target, span), expr(ExprKind::Int(1), span)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interp::Value;
+    use crate::test_util;
+
+    // the value `main` returns once `source` has been lowered and run
+    fn run(source: &str) -> Value {
+        test_util::run(source).unwrap()
+    }
+
+    #[test]
+    fn inclusive_ranges_can_end_at_the_largest_int() {
+        let source = "fn main() -> int {
+                int count = 0;
+                for i in 9223372036854775806..=9223372036854775807 { count = count + 1; }
+                return count;
+            }";
+        assert_eq!(run(source), Value::Int(2));
+    }
+
+    #[test]
+    fn ranges_count_up_from_their_start() {
+        let source = "fn main() -> list<int> {
+                list<int> seen = [];
+                for i in 0..3 { push(seen, i); }
+                for i in 5..=5 { push(seen, i); }
+                for i in 3..3 { push(seen, i); }
+                for i in 4..=3 { push(seen, i); }
+                return seen;
+            }";
+        assert_eq!(run(source), Value::List([0, 1, 2, 5].map(Value::Int).to_vec()));
+    }
+
+    #[test]
+    fn continue_moves_on_to_the_next_item() {
+        let source = "fn main() -> list<int> {
+                list<int> seen = [];
+                for i in 0..=4 { if i == 1 { continue; } if i == 3 { break; } push(seen, i); }
+                for x in [10, 20, 30] { if x == 20 { continue; } push(seen, x); }
+                return seen;
+            }";
+        assert_eq!(run(source), Value::List([0, 2, 10, 30].map(Value::Int).to_vec()));
+    }
+
+    #[test]
+    fn list_loops_walk_the_list_as_it_was() {
+        let source = "fn main() -> int {
+                list<int> xs = [1, 2, 3];
+                int total = 0;
+                for x in xs { total = total + x; push(xs, x); }
+                return total * 10 + len(xs);
+            }";
+        assert_eq!(run(source), Value::Int(66));
+    }
+}
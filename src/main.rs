@@ -1,47 +1,185 @@
-mod lex;
-mod parse;
-mod validate;
+use std::fs;
+use std::process::ExitCode;
 
-use parse::*;
-use lex::*;
-use validate::*;
+use simpl::diag::{ColorChoice, Diagnostic, Renderer};
+use simpl::lex::*;
+use simpl::parse::*;
+use simpl::validate::*;
+use simpl::{interp, lower, resolve, typeck};
 
-fn main() {
+const USAGE: &str = "\
+usage: simpl <command> <file.spl>
 
-    let input = "
-    struct MyStruct {
-        int x;
-        string y;
-    }
-    
-    fn main() -> null {
-        MyStruct z = MyStruct {
-            x: 0,
-            y: 1   
-        };
-    
-        print_int(z.y);
-
-        int x = 0;
+commands:
+    run      check a program and execute it
+    check    check a program without running it
+    tokens   print the token stream of a program
+    ast      print the syntax tree of a program
+
+options:
+    --color=WHEN     colour diagnostics: auto, always or never
+    -h, --help       print this message
+    -V, --version    print the version";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Command {
+    Run,
+    Check,
+    Tokens,
+    Ast,
+}
+
+// exit codes: 0 on success, 1 when the program is rejected, 2 on usage or io errors
+const EXIT_REJECTED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+
+// the passes recurse a few frames per level of nesting in the program, which the parser limits to
+// MAX_NESTING levels. unoptimised builds use more stack for that than the main thread has
+const STACK_SIZE: usize = 64 << 20;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let mut command = None;
+    let mut path = None;
+    let mut color = ColorChoice::Auto;
+
+    for arg in &args {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            "-V" | "--version" => {
+                println!("simpl {}", env!("CARGO_PKG_VERSION"));
+                return ExitCode::SUCCESS;
+            }
+            flag if flag.starts_with("--color=") => {
+                color = match &flag["--color=".len()..] {
+                    "auto" => ColorChoice::Auto,
+                    "always" => ColorChoice::Always,
+                    "never" => ColorChoice::Never,
+                    when => return usage_error(&format!("unknown colour choice `{}`", when)),
+                };
+            }
+            flag if flag.starts_with('-') => {
+                return usage_error(&format!("unknown option `{}`", flag));
+            }
+            word if command.is_none() => {
+                command = Some(match word {
+                    "run" => Command::Run,
+                    "check" => Command::Check,
+                    "tokens" => Command::Tokens,
+                    "ast" => Command::Ast,
+                    _ => return usage_error(&format!("unknown command `{}`", word)),
+                });
+            }
+            word if path.is_none() => path = Some(word.to_string()),
+            word => return usage_error(&format!("unexpected argument `{}`", word)),
+        }
     }
-";
-    let mut lexer = Lexer::new(input.to_string());
 
+    let Some(command) = command else {
+        return usage_error("no command given");
+    };
+    let Some(path) = path else {
+        return usage_error("no input file given");
+    };
+
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("simpl: could not read `{}`: {}", path, err);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let renderer = Renderer::new(&path, &source, color);
+    std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || drive(command, &renderer, source.clone()));
+        match thread.map(|thread| thread.join()) {
+            Ok(Ok(code)) => code,
+            Ok(Err(panic)) => std::panic::resume_unwind(panic),
+            Err(err) => {
+                eprintln!("simpl: could not start: {}", err);
+                ExitCode::from(EXIT_USAGE)
+            }
+        }
+    })
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("simpl: {}\n\n{}", message, USAGE);
+    ExitCode::from(EXIT_USAGE)
+}
+
+fn lex(source: String, diagnostics: &mut Vec<Diagnostic>) -> Vec<SpannedToken> {
     let mut tokens = Vec::new();
+    for result in Lexer::new(source) {
+        match result {
+            Ok(tok) => tokens.push(tok),
+            Err(err) => diagnostics.push(err.to_diagnostic()),
+        }
+    }
+    tokens
+}
+
+// print the diagnostics gathered so far, returning whether any of them stop the program
+fn report(renderer: &Renderer, diagnostics: &[Diagnostic]) -> bool {
+    renderer.emit(diagnostics);
+    diagnostics.iter().any(Diagnostic::is_error)
+}
+
+fn drive(command: Command, renderer: &Renderer, source: String) -> ExitCode {
+    let mut diagnostics = Vec::new();
+    let tokens = lex(source, &mut diagnostics);
 
-    loop {
-        let tok = lexer.next_token();
-        println!("{:?}", tok);
-        tokens.push(tok.clone());
+    if command == Command::Tokens {
+        for tok in &tokens {
+            println!("{} {:?}", tok.span, tok.token);
+        }
+        // the tokens around a bad character are still worth seeing, so they're printed first
+        if report(renderer, &diagnostics) {
+            return ExitCode::from(EXIT_REJECTED);
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let ast = match parse(tokens) {
+        Ok(ast) => ast,
+        Err(errors) => {
+            diagnostics.extend(errors.iter().map(ParseError::to_diagnostic));
+            report(renderer, &diagnostics);
+            return ExitCode::from(EXIT_REJECTED);
+        }
+    };
 
-        if tok == Token::EOF {
-            break;
+    if command == Command::Ast {
+        if report(renderer, &diagnostics) {
+            return ExitCode::from(EXIT_REJECTED);
         }
+        println!("{:#?}", ast);
+        return ExitCode::SUCCESS;
     }
 
-    let ast = parse(tokens);
+    let (resolution, resolve_diagnostics) = resolve::resolve(&ast);
+    diagnostics.extend(resolve_diagnostics);
+    diagnostics.extend(validate(&ast));
+    let (promoted, type_diagnostics) = typeck::check(&ast, &resolution);
+    diagnostics.extend(type_diagnostics);
+    if report(renderer, &diagnostics) {
+        return ExitCode::from(EXIT_REJECTED);
+    }
 
-    println!("{:#?}", ast);
+    if command == Command::Run {
+        let ast = lower::lower(ast);
+        if let Err(err) = interp::run(&ast, &promoted) {
+            report(renderer, &[err.to_diagnostic()]);
+            return ExitCode::from(EXIT_REJECTED);
+        }
+    }
 
-    println!("{}", validate(ast));
+    ExitCode::SUCCESS
 }
@@ -1,501 +1,1094 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::*;
+use crate::diag::{self, Diagnostic};
 use crate::lex::*;
 
-#[derive(Debug, PartialEq, Clone, Hash, Eq)]
-pub struct Node {
-    pub token: Token,
-    pub children: Vec<Node>,
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    // what the parser was looking for, e.g. "`;`" or "an expression"
+    pub expected: String,
+    pub found: Token,
+    pub span: Span,
+    // why the token can't appear here, when that isn't obvious from what was expected
+    pub note: Option<String>,
 }
 
-pub fn parse(toks: Vec<Token>) -> Node {
-    let mut nodes = Vec::new();
-    let mut i = 0;
-    while i < toks.len() {
-        let tok = toks[i].clone();
-        match tok {
-            Token::If => {
-                let mut children = Vec::new();
-                i += 1;
-                while toks[i] != Token::LeftCurlyBracket {
-                    children.push(Node {
-                        token: toks[i].clone(),
-                        children: Vec::new(),
-                    });
-                    i += 1;
-
-                    if i >= toks.len() {
-                        break;
-                    }
-                }
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.found {
+            Token::EOF => write!(f, "expected {}, found end of file", self.expected),
+            ref found => write!(f, "expected {}, found `{}`", self.expected, found),
+        }
+    }
+}
 
-                // now iterate until we find the matching right curly bracket, then append the statements as a block
-                let mut block = Vec::new();
-                let mut depth = 1;
-                i += 1;
-                if i < toks.len() {
-                    while depth > 0 {
-                        if toks[i] == Token::LeftCurlyBracket {
-                            depth += 1;
-                        } else if toks[i] == Token::RightCurlyBracket {
-                            depth -= 1;
-                        }
-                        if depth > 0 {
-                            let mut line = Vec::new();
-                            while toks[i] != Token::SemiColon {
-                                line.push(Node {
-                                    token: toks[i].clone(),
-                                    children: Vec::new(),
-                                });
-                                i += 1;
-
-                                if i >= toks.len() {
-                                    break;
-                                }
-                            }
+impl ParseError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let label = match self.found {
+            Token::EOF => format!("expected {} here", self.expected),
+            ref found => format!("unexpected `{}`", found),
+        };
+        let diag = Diagnostic::error(self.to_string())
+            .with_code(diag::SYNTAX_ERROR)
+            .with_label(self.span, label);
+        match &self.note {
+            Some(note) => diag.with_note(note.clone()),
+            None => diag,
+        }
+    }
+}
 
-                            line.push(
-                                Node {
-                                    token: Token::SemiColon,
-                                    children: Vec::new(),
-                                },
-                            );
+type Result<T> = std::result::Result<T, ParseError>;
+
+// how deeply statements, expressions, patterns and types can be nested. the parser and every pass
+// after it recurse once per level, so code nested without bound would overflow their stacks
+const MAX_NESTING: usize = 256;
+
+// parse a whole program, reporting every syntax error rather than stopping at the first
+pub fn parse(toks: Vec<SpannedToken>) -> std::result::Result<Program, Vec<ParseError>> {
+    // doc comments are taken out of the token stream and attached to the `fn`, `struct` or `enum`
+    // that follows them. anywhere else they are ordinary comments
+    let mut docs = HashMap::new();
+    let mut lines = Vec::new();
+    let mut kept = Vec::with_capacity(toks.len());
+    for tok in toks {
+        match tok.token {
+            Token::DocComment(text) => {
+                lines.push(text);
+                continue;
+            }
+            Token::Fn | Token::Struct | Token::Enum if !lines.is_empty() => {
+                docs.insert(kept.len(), lines.join("\n"));
+            }
+            _ => {}
+        }
+        lines.clear();
+        kept.push(tok);
+    }
 
+    let mut parser = Parser::new(kept, docs);
+    let program = parser.parse_program();
+    if parser.errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(parser.errors)
+    }
+}
 
-                            block.push(Node {
-                                token: Token::Line,
-                                children: line,
-                            });
-                        }
-                        i += 1;
+struct Parser {
+    toks: Vec<SpannedToken>,
+    pos: usize,
+    // the span of the last token consumed, where the node being parsed currently ends
+    last_span: Span,
+    // struct literals are not allowed directly in an if/while condition, where `name {` starts the block
+    allow_struct_literal: bool,
+    // doc comments, by the position of the `fn`, `struct` or `enum` token they document
+    docs: HashMap<usize, String>,
+    // how many levels deep the node being parsed is, which is at most MAX_NESTING
+    depth: usize,
+    errors: Vec<ParseError>,
+}
 
-                        if i >= toks.len() {
-                            break;
-                        }
-                    }
-                }
+impl Parser {
+    fn new(toks: Vec<SpannedToken>, docs: HashMap<usize, String>) -> Parser {
+        Parser {
+            toks,
+            pos: 0,
+            last_span: Span::default(),
+            allow_struct_literal: true,
+            docs,
+            depth: 0,
+            errors: Vec::new(),
+        }
+    }
 
-                // // remove the last token, which is the right curly bracket
-                // match block.pop() {
-                //     Some(
-                //         Node {
-                //             token: Token::RightCurlyBracket,
-                //             children: _,
-                //         }
-                //     ) => {}
-                //     Some(
-                //         Node {
-                //             token: Token::EOF,
-                //             children: _,
-                //         }
-                //     ) => {
-                //         block.pop();
-                //     }
-                //     _ => {}
-                // }
-
-
-                children.push(Node {
-                    token: Token::Block,
-                    children: block,
-                });
+    fn peek(&self) -> &Token {
+        self.peek_nth(0)
+    }
 
-                nodes.push(Node {
-                    token: Token::If,
-                    children,
-                });
+    fn peek_nth(&self, n: usize) -> &Token {
+        self.toks.get(self.pos + n).map_or(&Token::EOF, |t| &t.token)
+    }
+
+    fn peek_span(&self) -> Span {
+        match self.toks.get(self.pos) {
+            Some(tok) => tok.span,
+            None => self.toks.last().map(|t| t.span).unwrap_or_default(),
+        }
+    }
+
+    // the span from `start` to the end of the last token consumed
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.last_span)
+    }
+
+    fn advance(&mut self) -> Token {
+        let tok = self.peek().clone();
+        if self.pos < self.toks.len() {
+            self.last_span = self.toks[self.pos].span;
+            self.pos += 1;
+        }
+        tok
+    }
 
-                i -= 1;
+    fn eat(&mut self, want: &Token) -> bool {
+        if self.peek() == want {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    // an error for the current token, which is left in place
+    fn error(&self, expected: impl Into<String>) -> ParseError {
+        ParseError {
+            expected: expected.into(),
+            found: self.peek().clone(),
+            span: self.peek_span(),
+            note: None,
+        }
+    }
+
+    fn expect(&mut self, want: Token) -> Result<()> {
+        if self.eat(&want) {
+            Ok(())
+        } else {
+            Err(self.error(format!("`{}`", want)))
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<Ident> {
+        match self.peek() {
+            Token::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                Ok(Ident {
+                    name,
+                    span: self.last_span,
+                })
             }
-            Token::Else => {
-                // else functions much like if, but without the condition
-                let mut children = Vec::new();
-                i += 2; // once for the else, once for the left curly bracket
-                if i >= toks.len() {
-                    break;
+            _ => Err(self.error("an identifier")),
+        }
+    }
+
+    fn parse_program(&mut self) -> Program {
+        let mut items = Vec::new();
+        while *self.peek() != Token::EOF {
+            let item = match self.peek() {
+                Token::Fn => self.parse_fn(false).map(Item::Fn),
+                Token::Struct => self.parse_struct().map(Item::Struct),
+                Token::Enum => self.parse_enum().map(Item::Enum),
+                _ => Err(self.error("`fn`, `struct` or `enum`")),
+            };
+            match item {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    self.errors.push(err);
+                    self.synchronize_item();
                 }
+            }
+        }
+        Program { items }
+    }
 
-                // don't worry about being prefixed by if, we sort that out later
-
-                // now iterate until we find the matching right curly bracket, then append the statements as a block
-                let mut block = Vec::new();
-                let mut depth = 1;
-                if i < toks.len() {
-                    while depth > 0 {
-                        if toks[i] == Token::LeftCurlyBracket {
-                            depth += 1;
-                        } else if toks[i] == Token::RightCurlyBracket {
-                            depth -= 1;
-                        }
-                        if depth > 0 {
-                            let mut line = Vec::new();
-                            while toks[i] != Token::SemiColon {
-                                line.push(Node {
-                                    token: toks[i].clone(),
-                                    children: Vec::new(),
-                                });
-                                i += 1;
-
-                                if i >= toks.len() {
-                                    break;
-                                }
-                            }
-                            line.push(
-                                Node {
-                                    token: Token::SemiColon,
-                                    children: Vec::new(),
-                                },
-                            );
-
-                            block.push(Node {
-                                token: Token::Line,
-                                children: line,
-                            });
-                        }
-                        i += 1;
+    // after an error outside of any block, skip ahead to the start of the next item. a broken item
+    // can stop right at the next one, which is kept; every item consumes its keyword before failing
+    fn synchronize_item(&mut self) {
+        if !matches!(self.peek(), Token::Fn | Token::Struct | Token::Enum) {
+            self.advance();
+        }
+        while !matches!(self.peek(), Token::Fn | Token::Struct | Token::Enum | Token::EOF) {
+            self.advance();
+        }
+    }
 
-                        if i >= toks.len() {
-                            break;
-                        }
+    // after an error inside a block, skip past the end of the broken statement: the next `;` outside
+    // of any nested braces, or up to the `}` that closes the enclosing block
+    fn synchronize_stmt(&mut self, start: usize) {
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                Token::EOF => return,
+                Token::Fn | Token::Struct | Token::Enum if depth == 0 => return,
+                Token::SemiColon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                Token::RightCurlyBracket if depth == 0 => {
+                    // a stray `}` on the broken line itself must not end the block
+                    if self.pos == start {
+                        self.advance();
                     }
+                    return;
                 }
+                Token::LeftCurlyBracket => depth += 1,
+                Token::RightCurlyBracket => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
 
-                children.push(Node {
-                    token: Token::Block,
-                    children: block,
-                });
-
-                nodes.push(Node {
-                    token: Token::Else,
-                    children,
-                });
+    // fn name(type name, type name) -> type { ... }, where a missing return type means null. only
+    // a `method` declared in a struct can take `self` first
+    fn parse_fn(&mut self, method: bool) -> Result<FnDecl> {
+        let start = self.peek_span();
+        let doc = self.docs.remove(&self.pos);
+        self.expect(Token::Fn)?;
+        let name = self.expect_identifier()?;
+
+        self.expect(Token::LeftParen)?;
+        let mut receiver = None;
+        if *self.peek() == Token::SelfValue {
+            if !method {
+                let mut err = self.error("a parameter");
+                err.note = Some("only methods, declared inside a `struct`, can take `self`".to_string());
+                return Err(err);
+            }
+            self.advance();
+            receiver = Some(self.last_span);
+            if *self.peek() != Token::RightParen {
+                self.expect(Token::Comma)?;
+            }
+        }
+        let mut params = Vec::new();
+        while *self.peek() != Token::RightParen {
+            let ty = self.parse_type()?;
+            let name = self.expect_identifier()?;
+            params.push(Param {
+                span: ty.span.to(name.span),
+                ty,
+                name,
+            });
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(Token::RightParen)?;
+
+        let ret = if self.eat(&Token::Arrow) {
+            self.parse_type()?
+        } else {
+            TypeExpr {
+                kind: Type::Null,
+                span: self.span_from(start),
+            }
+        };
+
+        let body = self.parse_block()?;
+
+        Ok(FnDecl {
+            doc,
+            name,
+            receiver,
+            params,
+            ret,
+            body,
+            span: self.span_from(start),
+        })
+    }
 
-                i -= 1;
-            }
-            Token::LeftCurlyBracket => {
-                let mut children = Vec::new();
-                i += 1;
-                while toks[i] != Token::RightCurlyBracket {
-                    let mut line = Vec::new();
-                    while toks[i] != Token::SemiColon {
-                        line.push(Node {
-                            token: toks[i].clone(),
-                            children: Vec::new(),
-                        });
-                        i += 1;
-
-                        if i >= toks.len() {
-                            break;
-                        }
-                    }
-                    line.push(
-                        Node {
-                            token: Token::SemiColon,
-                            children: Vec::new(),
-                        },
-                    );
-                    children.push(Node {
-                        token: Token::Line,
-                        children: line,
-                    });
-                    i += 1;
-
-                    if i >= toks.len() {
-                        break;
+    // struct name { type name; type name; fn name(...) -> type { ... } }
+    fn parse_struct(&mut self) -> Result<StructDecl> {
+        let start = self.peek_span();
+        let doc = self.docs.remove(&self.pos);
+        self.expect(Token::Struct)?;
+        let name = self.expect_identifier()?;
+
+        self.expect(Token::LeftCurlyBracket)?;
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+        while !matches!(self.peek(), Token::RightCurlyBracket | Token::EOF | Token::Struct | Token::Enum) {
+            let start = self.pos;
+            if *self.peek() == Token::Fn {
+                match self.parse_fn(true) {
+                    Ok(method) => methods.push(method),
+                    Err(err) => {
+                        self.errors.push(err);
+                        self.synchronize_stmt(start);
                     }
                 }
-                nodes.push(Node {
-                    token: Token::Block,
-                    children,
-                });
-            }
-            Token::Struct => {
-                /*
-                struct name {
-                    type name;
-                    type name;
-                    fn name(type name, type name) -> type {
-                        ...
+            } else {
+                match self.parse_field() {
+                    Ok(field) => fields.push(field),
+                    Err(err) => {
+                        self.errors.push(err);
+                        self.synchronize_stmt(start);
                     }
                 }
-                 */
+            }
+        }
+        self.expect(Token::RightCurlyBracket)?;
+
+        Ok(StructDecl {
+            doc,
+            name,
+            fields,
+            methods,
+            span: self.span_from(start),
+        })
+    }
 
-                let mut children = Vec::new();
+    // type name;
+    fn parse_field(&mut self) -> Result<Field> {
+        let ty = self.parse_type()?;
+        let name = self.expect_identifier()?;
+        self.expect(Token::SemiColon)?;
+        Ok(Field {
+            span: self.span_from(ty.span),
+            ty,
+            name,
+        })
+    }
 
-                // push name to children
-                i += 1;
+    // enum name { Variant, Variant(type, type), Variant { name: type, name: type } }
+    fn parse_enum(&mut self) -> Result<EnumDecl> {
+        let start = self.peek_span();
+        let doc = self.docs.remove(&self.pos);
+        self.expect(Token::Enum)?;
+        let name = self.expect_identifier()?;
+
+        self.expect(Token::LeftCurlyBracket)?;
+        let mut variants = Vec::new();
+        while *self.peek() != Token::RightCurlyBracket {
+            variants.push(self.parse_variant()?);
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(Token::RightCurlyBracket)?;
+
+        Ok(EnumDecl {
+            doc,
+            name,
+            variants,
+            span: self.span_from(start),
+        })
+    }
 
-                children.push(Node { // name
-                    token: toks[i].clone(),
-                    children: Vec::new(),
+    fn parse_variant(&mut self) -> Result<Variant> {
+        let name = self.expect_identifier()?;
+        let fields = if self.eat(&Token::LeftParen) {
+            let mut types = Vec::new();
+            while *self.peek() != Token::RightParen {
+                types.push(self.parse_type()?);
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
+            }
+            self.expect(Token::RightParen)?;
+            VariantFields::Tuple(types)
+        } else if self.eat(&Token::LeftCurlyBracket) {
+            let mut fields = Vec::new();
+            while *self.peek() != Token::RightCurlyBracket {
+                let name = self.expect_identifier()?;
+                self.expect(Token::Colon)?;
+                let ty = self.parse_type()?;
+                fields.push(Field {
+                    span: name.span.to(ty.span),
+                    ty,
+                    name,
                 });
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
+            }
+            self.expect(Token::RightCurlyBracket)?;
+            VariantFields::Struct(fields)
+        } else {
+            VariantFields::Unit
+        };
+        Ok(Variant {
+            span: self.span_from(name.span),
+            name,
+            fields,
+        })
+    }
 
-                // now iterate until we find the matching right curly bracket, gathering the tokens. then, recursively parse them
-
-                let mut block = Vec::new();
-                let mut depth = 1;
-                i += 1;
-                if i < toks.len() {
-                    while depth > 0 {
-                        if toks[i] == Token::LeftCurlyBracket {
-                            depth += 1;
-                        } else if toks[i] == Token::RightCurlyBracket {
-                            depth -= 1;
-                        }
-                        if depth > 0 {
-                            block.push(toks[i].clone());
-                        }
-                        i += 1;
+    fn parse_type(&mut self) -> Result<TypeExpr> {
+        if matches!(self.peek(), Token::List | Token::Map) {
+            let start = self.peek_span();
+            let list = self.advance() == Token::List;
+            self.expect(Token::LessThan)?;
+            let first = Box::new(self.nested(Parser::parse_type)?);
+            let kind = if list {
+                Type::List(first)
+            } else {
+                self.expect(Token::Comma)?;
+                Type::Map(first, Box::new(self.nested(Parser::parse_type)?))
+            };
+            self.expect(Token::GreaterThan)?;
+            return Ok(TypeExpr {
+                kind,
+                span: self.span_from(start),
+            });
+        }
 
-                        if i >= toks.len() {
-                            break;
-                        }
-                    }
+        let kind = match self.peek() {
+            Token::Int => Type::Int,
+            Token::String => Type::String,
+            Token::Float => Type::Float,
+            Token::Bool => Type::Bool,
+            Token::Null => Type::Null,
+            Token::Identifier(name) => Type::Named(name.clone()),
+            _ => return Err(self.error("a type")),
+        };
+        self.advance();
+        Ok(TypeExpr {
+            kind,
+            span: self.last_span,
+        })
+    }
+
+    fn parse_block(&mut self) -> Result<Block> {
+        let start = self.peek_span();
+        self.expect(Token::LeftCurlyBracket)?;
+        let mut stmts = Vec::new();
+        // an item keyword means the closing `}` was forgotten, so leave it for the item loop
+        let item = |tok: &Token| matches!(tok, Token::Fn | Token::Struct | Token::Enum);
+        while !matches!(self.peek(), Token::RightCurlyBracket | Token::EOF) && !item(self.peek()) {
+            let start = self.pos;
+            match self.nested(Parser::parse_stmt) {
+                Ok(stmt) => stmts.push(stmt),
+                Err(err) => {
+                    self.errors.push(err);
+                    self.synchronize_stmt(start);
                 }
+            }
+        }
+        self.expect(Token::RightCurlyBracket)?;
+        Ok(Block {
+            stmts,
+            span: self.span_from(start),
+        })
+    }
 
-                // now remove the last token, which is the right curly bracket, and the first token, which is the left curly bracket
-                match block.pop() {
-                    Some(Token::RightCurlyBracket) => {}
-                    Some(Token::EOF) => {
-                        block.pop();
-                    }
-                    _ => {}
+    fn parse_stmt(&mut self) -> Result<Stmt> {
+        let start = self.peek_span();
+        let kind = match self.peek() {
+            Token::If => self.parse_if()?,
+            // branches are only parsed as part of an `if`, so one here has nothing to attach to. it is
+            // still parsed, so that errors after it are found as usual
+            Token::Else | Token::Elif => {
+                let mut err = self.error("a statement");
+                err.note = Some(format!("`{}` must directly follow the block of an `if` or `elif`", self.peek()));
+                self.errors.push(err);
+                if self.advance() == Token::Elif {
+                    self.parse_condition()?;
                 }
+                StmtKind::Block(self.parse_block()?)
+            }
+            Token::While => {
+                self.advance();
+                let cond = self.parse_condition()?;
+                let body = self.parse_block()?;
+                StmtKind::While { cond, body }
+            }
+            Token::For => {
+                self.advance();
+                let var = self.expect_identifier()?;
+                self.expect(Token::In)?;
+                let iter = self.parse_condition()?;
+                let body = self.parse_block()?;
+                StmtKind::For { var, iter, body }
+            }
+            Token::Return => {
+                self.advance();
+                let value = if *self.peek() == Token::SemiColon {
+                    None
+                } else {
+                    Some(self.parse_expr()?)
+                };
+                self.expect(Token::SemiColon)?;
+                StmtKind::Return(value)
+            }
+            Token::Break | Token::Continue => {
+                let kind = if self.advance() == Token::Break {
+                    StmtKind::Break
+                } else {
+                    StmtKind::Continue
+                };
+                self.expect(Token::SemiColon)?;
+                kind
+            }
+            Token::LeftCurlyBracket => StmtKind::Block(self.parse_block()?),
+            // a `match` that starts a statement can have blocks as arms, and needs no `;` after it
+            Token::Match => {
+                let (scrutinee, arms) = self.parse_match(true)?;
+                self.eat(&Token::SemiColon);
+                StmtKind::Match { scrutinee, arms }
+            }
+            Token::Int | Token::String | Token::Float | Token::Bool | Token::List | Token::Map => self.parse_let()?,
+            // `Name name` declares a variable of a struct type
+            Token::Identifier(_) if matches!(self.peek_nth(1), Token::Identifier(_)) => self.parse_let()?,
+            _ => {
+                let expr = self.parse_expr()?;
+                let kind = if self.eat(&Token::Equal) {
+                    let value = self.parse_expr()?;
+                    StmtKind::Assign {
+                        target: expr,
+                        value,
+                    }
+                } else {
+                    StmtKind::Expr(expr)
+                };
+                self.expect(Token::SemiColon)?;
+                kind
+            }
+        };
 
-                block.remove(0);
+        Ok(Stmt {
+            kind,
+            span: self.span_from(start),
+        })
+    }
 
-                // extract all the fields, then functions
-                let mut fields = Vec::new();
+    // type name = value; or type name;
+    fn parse_let(&mut self) -> Result<StmtKind> {
+        let ty = self.parse_type()?;
+        let name = self.expect_identifier()?;
+        let value = if self.eat(&Token::Equal) {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        self.expect(Token::SemiColon)?;
+        Ok(StmtKind::Let {
+            ty: Some(ty),
+            name,
+            value,
+        })
+    }
 
-                let mut j = 0;
-                while j < block.len() {
-                    if block[j] == Token::Fn {
-                        break;
-                    }
-                    fields.push(Node {
-                        token: block[j].clone(),
-                        children: Vec::new(),
-                    });
-                    j += 1;
-                }
+    fn parse_if(&mut self) -> Result<StmtKind> {
+        self.expect(Token::If)?;
+        let mut branches = vec![(self.parse_condition()?, self.parse_block()?)];
 
-                children.push(Node {
-                    token: Token::Fields,
-                    children: fields,
-                });
+        while self.eat(&Token::Elif) {
+            branches.push((self.parse_condition()?, self.parse_block()?));
+        }
 
-                // now extract the functions
-                let mut functions = Vec::new();
-                while j < block.len() {
-                    let mut func = Vec::new();
-                    while block[j] != Token::SemiColon {
-                        func.push(block[j].clone());
-                        j += 1;
-                    }
-                    func.push(Token::SemiColon);
-                    functions.push(parse(func));
-                    j += 1;
-                }
+        let otherwise = if self.eat(&Token::Else) {
+            Some(self.parse_block()?)
+        } else {
+            None
+        };
 
-                children.push(Node {
-                    token: Token::Functions,
-                    children: functions,
-                });
+        Ok(StmtKind::If {
+            branches,
+            otherwise,
+        })
+    }
 
-                nodes.push(Node {
-                    token: Token::Struct,
-                    children,
-                });
+    fn parse_condition(&mut self) -> Result<Expr> {
+        let allow = std::mem::replace(&mut self.allow_struct_literal, false);
+        let cond = self.parse_expr();
+        self.allow_struct_literal = allow;
+        cond
+    }
+
+    // match value { pattern => expr, pattern => { ... } }, where only a statement's arms can be blocks.
+    // an arm that is an expression needs a `,` after it unless it is the last
+    fn parse_match(&mut self, statement: bool) -> Result<(Expr, Vec<Arm>)> {
+        self.expect(Token::Match)?;
+        let scrutinee = self.parse_condition()?;
+        self.expect(Token::LeftCurlyBracket)?;
+        let mut arms = Vec::new();
+        while !matches!(self.peek(), Token::RightCurlyBracket | Token::EOF) {
+            let pattern = self.parse_pattern()?;
+            self.expect(Token::FatArrow)?;
+            let body = if statement && *self.peek() == Token::LeftCurlyBracket {
+                let block = self.parse_block()?;
+                self.eat(&Token::Comma);
+                ArmBody::Block(block)
+            } else {
+                let expr = self.parse_delimited(Parser::parse_expr)?;
+                if *self.peek() != Token::RightCurlyBracket {
+                    self.expect(Token::Comma)?;
+                }
+                ArmBody::Expr(expr)
+            };
+            arms.push(Arm {
+                span: self.span_from(pattern.span),
+                pattern,
+                body,
+            });
+        }
+        self.expect(Token::RightCurlyBracket)?;
+        Ok((scrutinee, arms))
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern> {
+        let start = self.peek_span();
+        let kind = match self.peek().clone() {
+            Token::Identifier(name) if name == "_" => {
+                self.advance();
+                PatternKind::Wildcard
             }
-            Token::Fn => {
-                // form: fn name(type arg, type arg) -> type { ... }
-                let mut children = Vec::new();
-                
-                // push name and arguments to children
-                i += 1;
-                children.push(Node { // name
-                    token: toks[i].clone(),
-                    children: Vec::new(),
-                });
+            Token::Identifier(_) => {
+                let name = self.expect_identifier()?;
+                if self.eat(&Token::DecimalPoint) {
+                    let variant = self.expect_identifier()?;
+                    let fields = if self.eat(&Token::LeftParen) {
+                        let mut patterns = Vec::new();
+                        while *self.peek() != Token::RightParen {
+                            patterns.push(self.nested(Parser::parse_pattern)?);
+                            if !self.eat(&Token::Comma) {
+                                break;
+                            }
+                        }
+                        self.expect(Token::RightParen)?;
+                        PatternFields::Tuple(patterns)
+                    } else if *self.peek() == Token::LeftCurlyBracket {
+                        PatternFields::Struct(self.parse_field_patterns()?)
+                    } else {
+                        PatternFields::Unit
+                    };
+                    PatternKind::Variant { name, variant, fields }
+                } else if *self.peek() == Token::LeftCurlyBracket {
+                    PatternKind::Struct {
+                        name,
+                        fields: self.parse_field_patterns()?,
+                    }
+                } else {
+                    PatternKind::Binding(name)
+                }
+            }
+            Token::Number(_)
+            | Token::FloatLiteral(_)
+            | Token::StringLiteral(_)
+            | Token::True
+            | Token::False
+            | Token::Null => PatternKind::Literal(self.parse_primary()?),
+            _ if self.at_smallest_int() => PatternKind::Literal(self.parse_smallest_int()),
+            Token::Minus if matches!(self.peek_nth(1), Token::Number(_) | Token::FloatLiteral(_)) => {
+                self.advance();
+                let expr = self.parse_primary()?;
+                PatternKind::Literal(Expr {
+                    kind: ExprKind::Unary {
+                        op: UnaryOp::Neg,
+                        expr: Box::new(expr),
+                    },
+                    span: self.span_from(start),
+                })
+            }
+            _ => return Err(self.error("a pattern")),
+        };
+        Ok(Pattern {
+            kind,
+            span: self.span_from(start),
+        })
+    }
 
-                i += 2; // skip left paren
-                let mut args = Vec::new();
-                while toks[i] != Token::RightParen {
-                    args.push(Node {
-                        token: toks[i].clone(),
-                        children: Vec::new(),
-                    });
-                    i += 1;
+    // { field: pattern, field }, where a field on its own binds a variable of the same name
+    fn parse_field_patterns(&mut self) -> Result<Vec<(Ident, Pattern)>> {
+        self.expect(Token::LeftCurlyBracket)?;
+        let mut fields = Vec::new();
+        while *self.peek() != Token::RightCurlyBracket {
+            let field = self.expect_identifier()?;
+            let pattern = if self.eat(&Token::Colon) {
+                self.nested(Parser::parse_pattern)?
+            } else {
+                Pattern {
+                    kind: PatternKind::Binding(field.clone()),
+                    span: field.span,
                 }
+            };
+            fields.push((field, pattern));
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(Token::RightCurlyBracket)?;
+        Ok(fields)
+    }
 
-                children.push(Node { // arguments
-                    token: Token::Arguments,
-                    children: args,
-                });
+    // ranges bind more loosely than any operator, and can't be chained
+    fn parse_expr(&mut self) -> Result<Expr> {
+        let start = self.peek_span();
+        let lhs = self.nested(|p| p.parse_expr_bp(0))?;
+        if !matches!(self.peek(), Token::DotDot | Token::DotDotEqual) {
+            return Ok(lhs);
+        }
+        let inclusive = self.advance() == Token::DotDotEqual;
+        let end = self.nested(|p| p.parse_expr_bp(0))?;
+        Ok(Expr {
+            kind: ExprKind::Range {
+                start: Box::new(lhs),
+                end: Box::new(end),
+                inclusive,
+            },
+            span: self.span_from(start),
+        })
+    }
 
-                // skip right paren and arrow
-                i += 2;
+    // pratt parsing: prefix operators and primaries first, then any postfix or infix operators
+    // whose left binding power is above `min_bp`
+    fn parse_expr_bp(&mut self, min_bp: u8) -> Result<Expr> {
+        let start = self.peek_span();
+        let mut lhs = match self.peek() {
+            _ if self.at_smallest_int() => self.parse_smallest_int(),
+            Token::Minus | Token::Bang => {
+                let op = if self.advance() == Token::Minus {
+                    UnaryOp::Neg
+                } else {
+                    UnaryOp::Not
+                };
+                let expr = self.nested(|p| p.parse_expr_bp(PREFIX_BP))?;
+                Expr {
+                    kind: ExprKind::Unary {
+                        op,
+                        expr: Box::new(expr),
+                    },
+                    span: self.span_from(start),
+                }
+            }
+            _ => self.parse_primary()?,
+        };
+
+        loop {
+            let postfix = matches!(
+                self.peek(),
+                Token::DecimalPoint | Token::LeftParen | Token::LeftSquareBracket
+            );
+            // each operator applied to `lhs` nests it a level deeper
+            if postfix && POSTFIX_BP > min_bp {
+                self.deeper()?;
+                lhs = self.parse_postfix(lhs, start)?;
+                continue;
+            }
 
-                // push return type to children
-                children.push(Node { // return type
-                    token: toks[i].clone(),
-                    children: Vec::new(),
-                });
+            let Some((op, l_bp, r_bp)) = infix_op(self.peek()) else {
+                break;
+            };
+            if l_bp <= min_bp {
+                break;
+            }
+            self.deeper()?;
+            self.advance();
+            let rhs = self.nested(|p| p.parse_expr_bp(r_bp))?;
+            lhs = Expr {
+                kind: ExprKind::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                span: self.span_from(start),
+            };
+        }
 
-                // now iterate until we find the matching right curly bracket, gathering the tokens. then, recursively parse them
-                let mut block = Vec::new();
-                let mut depth = 1;
-                i += 1;
-                if i < toks.len() {
-                    while depth > 0 {
-                        if toks[i] == Token::LeftCurlyBracket {
-                            depth += 1;
-                        } else if toks[i] == Token::RightCurlyBracket {
-                            depth -= 1;
-                        }
-                        if depth > 0 {
-                            block.push(toks[i].clone());
-                        }
-                        i += 1;
+        Ok(lhs)
+    }
 
-                        if i >= toks.len() {
-                            break;
-                        }
+    // field access, calls and indexing on `lhs`, which started at `start`. `Name.Variant {` can only
+    // build an enum variant with named fields
+    fn parse_postfix(&mut self, lhs: Expr, start: Span) -> Result<Expr> {
+        let lhs = Box::new(lhs);
+        let kind = match self.advance() {
+            Token::DecimalPoint => {
+                let field = self.expect_identifier()?;
+                let variant = self.allow_struct_literal && *self.peek() == Token::LeftCurlyBracket;
+                match &lhs.kind {
+                    ExprKind::Ident(name) if variant => {
+                        let name = Ident {
+                            name: name.clone(),
+                            span: lhs.span,
+                        };
+                        self.parse_struct_literal(name, Some(field))?
                     }
+                    _ => ExprKind::Field { expr: lhs, field },
                 }
+            }
+            Token::LeftParen => ExprKind::Call {
+                callee: lhs,
+                args: self.parse_args()?,
+            },
+            Token::LeftSquareBracket => {
+                let index = self.parse_delimited(Parser::parse_expr)?;
+                self.expect(Token::RightSquareBracket)?;
+                ExprKind::Index {
+                    expr: lhs,
+                    index: Box::new(index),
+                }
+            }
+            tok => unreachable!("{} is not a postfix operator", tok),
+        };
+        Ok(Expr {
+            kind,
+            span: self.span_from(start),
+        })
+    }
 
-                // now remove the last token, which is the right curly bracket, and the first token, which is the left curly bracket
-                match block.pop() {
-                    Some(Token::RightCurlyBracket) => {}
-                    Some(Token::EOF) => {
-                        block.pop();
-                    }
-                    _ => {}
+    // run `f` a level deeper than the node being parsed
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Parser) -> Result<T>) -> Result<T> {
+        let depth = self.depth;
+        let result = self.deeper().and_then(|()| f(self));
+        self.depth = depth;
+        result
+    }
+
+    // go a level deeper, until the code is nested too deeply to parse. whatever calls `nested` puts
+    // the depth back
+    fn deeper(&mut self) -> Result<()> {
+        if self.depth == MAX_NESTING {
+            let mut err = self.error(format!("at most {} levels of nesting", MAX_NESTING));
+            err.note = Some("split the code up with variables or functions".to_string());
+            return Err(err);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    // run `f` inside brackets, where struct literals are unambiguous again
+    fn parse_delimited<T>(&mut self, f: impl FnOnce(&mut Parser) -> Result<T>) -> Result<T> {
+        let allow = std::mem::replace(&mut self.allow_struct_literal, true);
+        let result = f(self);
+        self.allow_struct_literal = allow;
+        result
+    }
+
+    // the arguments of a call, with the left paren already consumed
+    fn parse_args(&mut self) -> Result<Vec<Expr>> {
+        self.parse_exprs(Token::RightParen)
+    }
+
+    // comma separated expressions up to and including `close`, which may follow a trailing comma
+    fn parse_exprs(&mut self, close: Token) -> Result<Vec<Expr>> {
+        let exprs = self.parse_delimited(|p| {
+            let mut exprs = Vec::new();
+            while *p.peek() != close {
+                exprs.push(p.parse_expr()?);
+                if !p.eat(&Token::Comma) {
+                    break;
                 }
+            }
+            Ok(exprs)
+        })?;
+        self.expect(close)?;
+        Ok(exprs)
+    }
 
+    // `-9223372036854775808`, whose digits alone are too large for an int, so it is taken as one
+    // literal rather than as `-` applied to one
+    fn at_smallest_int(&self) -> bool {
+        *self.peek() == Token::Minus && *self.peek_nth(1) == Token::Number(i64::MIN.unsigned_abs())
+    }
 
-                // parse the block
-                children.push(Node {
-                    token: Token::Block,
-                    children: vec![parse(block)],
-                });
+    fn parse_smallest_int(&mut self) -> Expr {
+        let start = self.peek_span();
+        self.advance();
+        self.advance();
+        Expr {
+            kind: ExprKind::Int(i64::MIN),
+            span: self.span_from(start),
+        }
+    }
 
+    fn parse_primary(&mut self) -> Result<Expr> {
+        let start = self.peek_span();
+        if *self.peek() == Token::Match {
+            let (scrutinee, arms) = self.parse_match(false)?;
+            return Ok(Expr {
+                kind: ExprKind::Match {
+                    scrutinee: Box::new(scrutinee),
+                    arms,
+                },
+                span: self.span_from(start),
+            });
+        }
+        let tok = match self.peek() {
+            Token::Number(n) if i64::try_from(*n).is_err() => {
+                let mut err = self.error(format!("an int no larger than {}", i64::MAX));
+                err.note = Some(format!("only `-{}`, the smallest int, can be written with these digits", n));
+                return Err(err);
+            }
+            Token::Number(_)
+            | Token::FloatLiteral(_)
+            | Token::StringLiteral(_)
+            | Token::True
+            | Token::False
+            | Token::Null
+            | Token::SelfValue
+            | Token::LeftParen
+            | Token::LeftSquareBracket
+            | Token::Identifier(_) => self.advance(),
+            // a `{` at the start of a statement opens a block, and one after a condition opens its
+            // body, so anywhere else it is a map literal
+            Token::LeftCurlyBracket if self.allow_struct_literal => self.advance(),
+            _ => return Err(self.error("an expression")),
+        };
+        let kind = match tok {
+            Token::Number(n) => ExprKind::Int(n as i64),
+            Token::FloatLiteral(x) => ExprKind::Float(x),
+            Token::StringLiteral(value) => ExprKind::Str(value),
+            Token::True => ExprKind::Bool(true),
+            Token::False => ExprKind::Bool(false),
+            Token::Null => ExprKind::Null,
+            Token::SelfValue => ExprKind::Ident("self".to_string()),
+            Token::LeftParen => {
+                let expr = self.parse_delimited(Parser::parse_expr)?;
+                self.expect(Token::RightParen)?;
+                // keep the parentheses in the span so errors point at the whole group
+                expr.kind
+            }
+            Token::LeftSquareBracket => ExprKind::List(self.parse_exprs(Token::RightSquareBracket)?),
+            Token::LeftCurlyBracket => self.parse_map_literal()?,
+            Token::Identifier(name) => {
+                if self.allow_struct_literal && *self.peek() == Token::LeftCurlyBracket {
+                    self.parse_struct_literal(Ident { name, span: start }, None)?
+                } else {
+                    ExprKind::Ident(name)
+                }
+            }
+            _ => unreachable!("{} does not start an expression", tok),
+        };
+        Ok(Expr {
+            kind,
+            span: self.span_from(start),
+        })
+    }
 
-                nodes.push(Node {
-                    token: Token::Fn,
-                    children,
-                });
+    // { key: value, key: value }, with the left brace already consumed
+    fn parse_map_literal(&mut self) -> Result<ExprKind> {
+        let entries = self.parse_delimited(|p| {
+            let mut entries = Vec::new();
+            while *p.peek() != Token::RightCurlyBracket {
+                let key = p.parse_expr()?;
+                p.expect(Token::Colon)?;
+                entries.push((key, p.parse_expr()?));
+                if !p.eat(&Token::Comma) {
+                    break;
+                }
             }
-            Token::Identifier(_) => {
-                // if it is followed by a LeftCurlyBracket, it is a struct definition
-
-                let next = toks[i + 1].clone();
-
-                println!("currently on {:?}", toks[i]);
-                println!("next is {:?}", next);
-
-                match next {
-                    Token::LeftCurlyBracket => {
-                        // handle struct definition
-                        let mut children = Vec::new();
-
-                        // push name to children
-                        children.push(Node { // name
-                            token: toks[i].clone(),
-                            children: Vec::new(),
-                        });
-
-                        // now iterate until we find the matching right curly bracket, gathering the tokens. there will be no functions, so we don't need to parse them
-                        let mut block = Vec::new();
-                        let mut depth = 1;
-                        i += 2;
-
-                        if i < toks.len() {
-                            while depth > 0 {
-                                if toks[i] == Token::LeftCurlyBracket {
-                                    depth += 1;
-                                } else if toks[i] == Token::RightCurlyBracket {
-                                    depth -= 1;
-                                }
-                                if depth > 0 {
-                                    block.push(toks[i].clone());
-                                }
-                                i += 1;
-
-                                if i >= toks.len() {
-                                    break;
-                                }
-                            }
-                        }
+            Ok(entries)
+        })?;
+        self.expect(Token::RightCurlyBracket)?;
+        Ok(ExprKind::Map(entries))
+    }
 
-                        // now remove the last token, which is the right curly bracket, and the first token, which is the left curly bracket
+    // Name { field: value, field: value }, or Enum.Variant { ... } when there is a `variant`
+    fn parse_struct_literal(&mut self, name: Ident, variant: Option<Ident>) -> Result<ExprKind> {
+        self.expect(Token::LeftCurlyBracket)?;
+        let mut fields = Vec::new();
+        while *self.peek() != Token::RightCurlyBracket {
+            let field = self.expect_identifier()?;
+            self.expect(Token::Colon)?;
+            fields.push((field, self.parse_expr()?));
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(Token::RightCurlyBracket)?;
+        Ok(ExprKind::StructLit { name, variant, fields })
+    }
+}
 
-                        match block.pop() {
-                            Some(Token::RightCurlyBracket) => {}
-                            Some(Token::EOF) => {
-                                block.pop();
-                            }
-                            _ => {}
-                        }
+// binding powers, loosest first. infix operators are left associative, so their right
+// power is one above their left
+const PREFIX_BP: u8 = 13;
+const POSTFIX_BP: u8 = 15;
+
+fn infix_op(tok: &Token) -> Option<(BinaryOp, u8, u8)> {
+    let (op, bp) = match tok {
+        Token::DoublePipe => (BinaryOp::Or, 1),
+        Token::DoubleAmpersand => (BinaryOp::And, 3),
+        Token::DoubleEqual => (BinaryOp::Eq, 5),
+        Token::NotEqual => (BinaryOp::Ne, 5),
+        Token::LessThan => (BinaryOp::Lt, 7),
+        Token::LessThanOrEqual => (BinaryOp::Le, 7),
+        Token::GreaterThan => (BinaryOp::Gt, 7),
+        Token::GreaterThanOrEqual => (BinaryOp::Ge, 7),
+        Token::Plus => (BinaryOp::Add, 9),
+        Token::Minus => (BinaryOp::Sub, 9),
+        Token::Star => (BinaryOp::Mul, 11),
+        Token::Slash => (BinaryOp::Div, 11),
+        _ => return None,
+    };
+    Some((op, bp, bp + 1))
+}
 
-                        block.remove(0);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interp::Value;
+    use crate::test_util::{run, tokens};
 
-                        // extract all the fields
-                        let mut fields = Vec::new();
+    // the errors `source` fails to parse with, as the lines they are on
+    fn errors(source: &str) -> Vec<usize> {
+        parse(tokens(source)).unwrap_err().iter().map(|err| err.span.line).collect()
+    }
 
-                        let mut j = 0;
+    #[test]
+    fn the_smallest_int_can_be_written() {
+        let source = "fn main() -> int { return -9223372036854775808 + 0; }";
+        assert_eq!(run(source).unwrap(), Value::Int(i64::MIN));
+        let source = "fn f(int n) { match n { -9223372036854775808 => print(n), _ => print(0) } }";
+        assert!(parse(tokens(source)).is_ok());
+        assert_eq!(errors("fn main() {\n int x = 9223372036854775808;\n}"), vec![2]);
+        assert_eq!(errors("fn main() {\n int x = 1 - 9223372036854775808;\n}"), vec![2]);
+    }
 
-                        while j < block.len() {
-                            fields.push(Node {
-                                token: block[j].clone(),
-                                children: Vec::new(),
-                            });
-                            println!("pushing {:?}", toks[i]);
-                            j += 1;
-                        }
+    #[test]
+    fn an_unclosed_item_keeps_the_next_one() {
+        assert_eq!(errors("fn a() {\n fn b() { int x = ; }"), vec![2, 2]);
+    }
 
-                        children.push(Node {
-                            token: Token::Fields,
-                            children: fields,
-                        });
+    #[test]
+    fn each_broken_statement_is_reported() {
+        assert_eq!(errors("fn a() {\n int x = ;\n int y = 1;\n y = * 2;\n}"), vec![2, 4]);
+        assert_eq!(errors("fn a() {\n if { }\n}\nfn b() {\n int x = ;\n}"), vec![2, 5]);
+    }
 
-                        nodes.push(Node {
-                            token: Token::Struct,
-                            children,
-                        });
-                    }
-                    _ => {}
-                }
+    #[test]
+    fn nesting_is_limited() {
+        // the binary parses on a thread with a 64 MiB stack, since an unoptimised build needs a few MiB
+        // for code nested as deeply as the parser allows
+        let thread = std::thread::Builder::new().stack_size(64 << 20).spawn(|| {
+            let nest = |open: &str, inner: &str, close: &str, n| open.repeat(n) + inner + &close.repeat(n);
+            let source = format!("fn main() -> int {{ return {}; }}", nest("(", "1", ")", 120));
+            assert_eq!(run(&source).unwrap(), Value::Int(1));
+            for source in [
+                format!("fn main() {{ int x = {}; }}", nest("(", "1", ")", 1000)),
+                format!("fn main() {{ int x = {}; }}", nest("-", "1", "", 1000)),
+                format!("fn main() {{ int x = 1{}; }}", " + 1".repeat(1000)),
+                format!("fn main() {{ int x = len(x){}; }}", "[0]".repeat(1000)),
+                format!("fn main() {{ {} }}", nest("if true { ", "", " }", 1000)),
+                format!("fn f({} x) {{}}", nest("list<", "int", ">", 1000)),
+                format!("fn f(O o) {{ match o {{ {} => print(1) }} }}", nest("O.S(", "_", ")", 1000)),
+            ] {
+                let errors = parse(tokens(&source)).unwrap_err();
+                assert!(errors[0].expected.starts_with("at most"), "{}", errors[0]);
             }
-            _ => {
-                // ensure to split lines by semicolons
-                let mut line = Vec::new();
-                while toks[i] != Token::SemiColon {
-                    line.push(Node {
-                        token: toks[i].clone(),
-                        children: Vec::new(),
-                    });
-                    i += 1;
-
-                    if i >= toks.len() {
-                        break;
-                    }
-                }
-                line.push(
-                    Node {
-                        token: Token::SemiColon,
-                        children: Vec::new(),
-                    },
-                );
-                nodes.push(Node {
-                    token: Token::Line,
-                    children: line,
-                });
-                
+        });
+        thread.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn branches_are_tried_in_order() {
+        let source = "fn f(int n) -> int {
+                if n < 0 { return 0; } elif n < 10 { return 1; } elif n < 100 { return 2; } else { return 3; }
             }
-        }
-        i += 1;
+            fn main() -> list<int> { return [f(-1), f(5), f(50), f(500)]; }";
+        assert_eq!(run(source).unwrap(), Value::List([0, 1, 2, 3].map(Value::Int).to_vec()));
+    }
+
+    #[test]
+    fn else_and_elif_need_an_if() {
+        assert_eq!(errors("fn main() {\n else { }\n}"), vec![2]);
+        assert_eq!(errors("fn main() {\n if true { }\n print(1);\n elif false { }\n}"), vec![4]);
+        assert_eq!(errors("fn main() {\n if true { } else { }\n else { }\n}"), vec![3]);
+        let err = &parse(tokens("fn main() { else { } }")).unwrap_err()[0];
+        assert_eq!(err.note.as_deref(), Some("`else` must directly follow the block of an `if` or `elif`"));
+
+        // the branch is still parsed, so the errors inside it are found too
+        assert_eq!(errors("fn main() {\n else {\n int x = ;\n }\n}"), vec![2, 3]);
     }
-    Node {
-        token: Token::Program,
-        children: nodes,
+
+    #[test]
+    fn junk_between_items_is_skipped() {
+        assert_eq!(errors("fn a() {}\n1 + 2;\nstruct S { int x }\nfn b() { return; }\n}"), vec![2, 3, 5]);
     }
 }
@@ -0,0 +1,557 @@
+// name resolution: works out which declaration every name in the program refers to, reporting
+// names that don't exist, names declared twice in one scope and locals that shadow outer ones
+
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diag::{self, Diagnostic};
+use crate::lex::Span;
+
+// the functions every program can call without declaring them
+pub const BUILTINS: &[&str] = &[
+    "print", "print_int", "to_int", "len", "push", "pop", "insert", "remove", "contains", "keys", "values",
+];
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum DeclKind {
+    Function,
+    Builtin,
+    Struct,
+    Field,
+    Method,
+    Enum,
+    Variant,
+    Param,
+    Local,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Decl {
+    pub kind: DeclKind,
+    pub name: String,
+    // where the name is declared, which built-ins don't have
+    pub span: Option<Span>,
+}
+
+pub type DeclId = usize;
+
+// every declaration in the program, and the declaration each name in the source resolves to.
+// names are keyed by their span, which is unique to each occurrence
+#[derive(Debug, Default)]
+pub struct Resolution {
+    pub decls: Vec<Decl>,
+    pub names: HashMap<Span, DeclId>,
+}
+
+impl Resolution {
+    // the declaration that the name at `span` refers to, or declares
+    pub fn decl_at(&self, span: Span) -> Option<DeclId> {
+        self.names.get(&span).copied()
+    }
+}
+
+pub fn resolve(program: &Program) -> (Resolution, Vec<Diagnostic>) {
+    let mut resolver = Resolver {
+        res: Resolution::default(),
+        functions: HashMap::new(),
+        structs: HashMap::new(),
+        enums: HashMap::new(),
+        scopes: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    for &name in BUILTINS {
+        let id = resolver.add_decl(DeclKind::Builtin, name, None);
+        resolver.functions.insert(name.to_string(), id);
+    }
+
+    // items can be used before they are declared, so they all go in scope first
+    let mut items: HashMap<String, DeclId> = HashMap::new();
+    for item in &program.items {
+        let (kind, name) = match item {
+            Item::Fn(function) => (DeclKind::Function, &function.name),
+            Item::Struct(decl) => (DeclKind::Struct, &decl.name),
+            Item::Enum(decl) => (DeclKind::Enum, &decl.name),
+        };
+        let id = resolver.define(&mut items, kind, name);
+        // a call by this name would still reach the built-in, leaving the function unused
+        if kind == DeclKind::Function && BUILTINS.contains(&name.name.as_str()) {
+            resolver.diagnostics.push(
+                Diagnostic::error(format!("`{}` is defined more than once", name.name))
+                    .with_code(diag::DUPLICATE_DEFINITION)
+                    .with_label(name.span, "defined again here")
+                    .with_note(format!("`{}` is a built-in function", name.name)),
+            );
+        }
+        match kind {
+            DeclKind::Function => resolver.functions.entry(name.name.clone()).or_insert(id),
+            DeclKind::Struct => resolver.structs.entry(name.name.clone()).or_insert(id),
+            _ => resolver.enums.entry(name.name.clone()).or_insert(id),
+        };
+    }
+
+    for item in &program.items {
+        match item {
+            Item::Fn(function) => resolver.resolve_fn(function),
+            Item::Struct(decl) => {
+                let mut members = HashMap::new();
+                for field in &decl.fields {
+                    resolver.resolve_type(&field.ty);
+                    resolver.define(&mut members, DeclKind::Field, &field.name);
+                }
+                for method in &decl.methods {
+                    resolver.define(&mut members, DeclKind::Method, &method.name);
+                }
+                for method in &decl.methods {
+                    resolver.resolve_fn(method);
+                }
+            }
+            Item::Enum(decl) => {
+                let mut variants = HashMap::new();
+                for variant in &decl.variants {
+                    resolver.define(&mut variants, DeclKind::Variant, &variant.name);
+                    match &variant.fields {
+                        VariantFields::Unit => {}
+                        VariantFields::Tuple(types) => {
+                            for ty in types {
+                                resolver.resolve_type(ty);
+                            }
+                        }
+                        VariantFields::Struct(fields) => {
+                            let mut members = HashMap::new();
+                            for field in fields {
+                                resolver.resolve_type(&field.ty);
+                                resolver.define(&mut members, DeclKind::Field, &field.name);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    (resolver.res, resolver.diagnostics)
+}
+
+struct Resolver {
+    res: Resolution,
+    functions: HashMap<String, DeclId>,
+    structs: HashMap<String, DeclId>,
+    enums: HashMap<String, DeclId>,
+    // the parameters and nested blocks of the function being resolved, innermost last
+    scopes: Vec<HashMap<String, DeclId>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    fn add_decl(&mut self, kind: DeclKind, name: &str, span: Option<Span>) -> DeclId {
+        self.res.decls.push(Decl {
+            kind,
+            name: name.to_string(),
+            span,
+        });
+        self.res.decls.len() - 1
+    }
+
+    // declare `name` in `scope`, reporting it if the scope already has something by that name
+    fn define(&mut self, scope: &mut HashMap<String, DeclId>, kind: DeclKind, name: &Ident) -> DeclId {
+        let id = self.add_decl(kind, &name.name, Some(name.span));
+        self.res.names.insert(name.span, id);
+
+        if let Some(&first) = scope.get(&name.name) {
+            let mut diag = Diagnostic::error(format!("`{}` is defined more than once", name.name))
+                .with_code(diag::DUPLICATE_DEFINITION)
+                .with_label(name.span, "defined again here");
+            if let Some(span) = self.res.decls[first].span {
+                diag = diag.with_secondary(span, "first defined here");
+            }
+            self.diagnostics.push(diag);
+        } else {
+            scope.insert(name.name.clone(), id);
+        }
+        id
+    }
+
+    // declare a parameter or local in the innermost scope, warning when it hides an outer one
+    fn define_local(&mut self, kind: DeclKind, name: &Ident) {
+        let outer = self.lookup_local(&name.name);
+
+        let mut scope = self.scopes.pop().unwrap_or_default();
+        let redefined = scope.contains_key(&name.name);
+        self.define(&mut scope, kind, name);
+        self.scopes.push(scope);
+
+        if let (Some(outer), false) = (outer, redefined) {
+            let mut diag = Diagnostic::warning(format!("`{}` shadows an earlier declaration", name.name))
+                .with_label(name.span, "this hides the outer one");
+            if let Some(span) = self.res.decls[outer].span {
+                diag = diag.with_secondary(span, "shadowed declaration");
+            }
+            self.diagnostics.push(diag.with_help("consider renaming one of them"));
+        }
+    }
+
+    fn lookup_local(&self, name: &str) -> Option<DeclId> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    fn resolve_type(&mut self, ty: &TypeExpr) {
+        let name = match &ty.kind {
+            Type::Named(name) => name,
+            Type::List(item) => return self.resolve_type(item),
+            Type::Map(key, value) => {
+                // keys are compared exactly, which floats, lists, maps and structs can't be
+                if !matches!(key.kind, Type::Int | Type::String | Type::Bool) {
+                    self.diagnostics.push(
+                        Diagnostic::error("unsupported map key type")
+                            .with_code(diag::UNSUPPORTED_OPERATION)
+                            .with_label(key.span, "maps are keyed by `int`, `string` or `bool`"),
+                    );
+                }
+                self.resolve_type(key);
+                return self.resolve_type(value);
+            }
+            _ => return,
+        };
+        match self.structs.get(name).or(self.enums.get(name)) {
+            Some(&id) => {
+                self.res.names.insert(ty.span, id);
+            }
+            None => self.diagnostics.push(
+                Diagnostic::error(format!("unknown type `{}`", name))
+                    .with_code(diag::UNKNOWN_TYPE)
+                    .with_label(ty.span, "not a built-in type, struct or enum"),
+            ),
+        }
+    }
+
+    // the struct or enum that `object` names when it is used like `Name.member`, unless a local has
+    // the same name
+    fn owner(&self, object: &Expr) -> Option<DeclId> {
+        match &object.kind {
+            ExprKind::Ident(name) if self.lookup_local(name).is_none() => {
+                self.structs.get(name).or(self.enums.get(name)).copied()
+            }
+            _ => None,
+        }
+    }
+
+    fn resolve_enum_name(&mut self, name: &Ident) {
+        match self.enums.get(&name.name) {
+            Some(&id) => {
+                self.res.names.insert(name.span, id);
+            }
+            None => self.diagnostics.push(
+                Diagnostic::error(format!("cannot find enum `{}`", name.name))
+                    .with_code(diag::UNKNOWN_TYPE)
+                    .with_label(name.span, "not an enum"),
+            ),
+        }
+    }
+
+    fn resolve_struct_name(&mut self, name: &Ident) {
+        match self.structs.get(&name.name) {
+            Some(&id) => {
+                self.res.names.insert(name.span, id);
+            }
+            None => self.diagnostics.push(
+                Diagnostic::error(format!("`{}` is not a struct", name.name))
+                    .with_code(diag::UNKNOWN_STRUCT)
+                    .with_label(name.span, "not a struct"),
+            ),
+        }
+    }
+
+    // each arm is its own scope, holding what its pattern binds
+    fn resolve_arms(&mut self, scrutinee: &Expr, arms: &[Arm]) {
+        self.resolve_expr(scrutinee);
+        for arm in arms {
+            self.scopes.push(HashMap::new());
+            self.resolve_pattern(&arm.pattern);
+            match &arm.body {
+                ArmBody::Expr(expr) => self.resolve_expr(expr),
+                ArmBody::Block(body) => self.resolve_stmts(&body.stmts),
+            }
+            self.scopes.pop();
+        }
+    }
+
+    fn resolve_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Literal(_) => {}
+            PatternKind::Binding(name) => self.define_local(DeclKind::Local, name),
+            PatternKind::Variant { name, fields, .. } => {
+                self.resolve_enum_name(name);
+                match fields {
+                    PatternFields::Unit => {}
+                    PatternFields::Tuple(patterns) => {
+                        for pattern in patterns {
+                            self.resolve_pattern(pattern);
+                        }
+                    }
+                    PatternFields::Struct(fields) => {
+                        for (_, pattern) in fields {
+                            self.resolve_pattern(pattern);
+                        }
+                    }
+                }
+            }
+            PatternKind::Struct { name, fields } => {
+                self.resolve_struct_name(name);
+                for (_, pattern) in fields {
+                    self.resolve_pattern(pattern);
+                }
+            }
+        }
+    }
+
+    fn resolve_fn(&mut self, function: &FnDecl) {
+        self.scopes = vec![HashMap::new()];
+        if let Some(span) = function.receiver {
+            let name = Ident {
+                name: "self".to_string(),
+                span,
+            };
+            self.define_local(DeclKind::Param, &name);
+        }
+        for param in &function.params {
+            self.resolve_type(&param.ty);
+            self.define_local(DeclKind::Param, &param.name);
+        }
+        self.resolve_type(&function.ret);
+
+        // the body shares the parameters' scope, so a local can't silently replace a parameter
+        self.resolve_stmts(&function.body.stmts);
+        self.scopes.clear();
+    }
+
+    fn resolve_block(&mut self, block: &Block) {
+        self.scopes.push(HashMap::new());
+        self.resolve_stmts(&block.stmts);
+        self.scopes.pop();
+    }
+
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::Let { ty, name, value } => {
+                    if let Some(ty) = ty {
+                        self.resolve_type(ty);
+                    }
+                    // the value is resolved first, since `int x = x;` can't refer to itself
+                    if let Some(value) = value {
+                        self.resolve_expr(value);
+                    }
+                    self.define_local(DeclKind::Local, name);
+                }
+                StmtKind::Assign { target, value } => {
+                    self.resolve_expr(target);
+                    self.resolve_expr(value);
+                }
+                StmtKind::If {
+                    branches,
+                    otherwise,
+                } => {
+                    for (cond, body) in branches {
+                        self.resolve_expr(cond);
+                        self.resolve_block(body);
+                    }
+                    if let Some(body) = otherwise {
+                        self.resolve_block(body);
+                    }
+                }
+                StmtKind::While { cond, body } => {
+                    self.resolve_expr(cond);
+                    self.resolve_block(body);
+                }
+                // the body shares the loop variable's scope, like a function body and its parameters
+                StmtKind::For { var, iter, body } => {
+                    self.resolve_expr(iter);
+                    self.scopes.push(HashMap::new());
+                    self.define_local(DeclKind::Local, var);
+                    self.resolve_stmts(&body.stmts);
+                    self.scopes.pop();
+                }
+                StmtKind::Match { scrutinee, arms } => self.resolve_arms(scrutinee, arms),
+                StmtKind::Return(value) => {
+                    if let Some(value) = value {
+                        self.resolve_expr(value);
+                    }
+                }
+                StmtKind::Break | StmtKind::Continue => {}
+                StmtKind::Block(body) => self.resolve_block(body),
+                StmtKind::Expr(expr) => self.resolve_expr(expr),
+            }
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::Str(_) | ExprKind::Null => {}
+            ExprKind::Ident(name) => match self.lookup_local(name) {
+                Some(id) => {
+                    self.res.names.insert(expr.span, id);
+                }
+                None => {
+                    let mut diag = Diagnostic::error(format!("cannot find variable `{}` in this scope", name))
+                        .with_code(diag::UNDEFINED_VARIABLE)
+                        .with_label(expr.span, "not found in this scope");
+                    if self.functions.contains_key(name) {
+                        diag = diag.with_note(format!("`{}` is a function, which can only be called", name));
+                    } else if name == "self" {
+                        diag = diag.with_note("`self` is only available in methods that take it");
+                    }
+                    self.diagnostics.push(diag);
+                }
+            },
+            ExprKind::List(items) => {
+                for item in items {
+                    self.resolve_expr(item);
+                }
+            }
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            }
+            ExprKind::Unary { expr, .. } => self.resolve_expr(expr),
+            ExprKind::Binary { lhs, rhs, .. } => {
+                self.resolve_expr(lhs);
+                self.resolve_expr(rhs);
+            }
+            ExprKind::Call { callee, args } => {
+                match &callee.kind {
+                    ExprKind::Ident(name) => match self.functions.get(name) {
+                        Some(&id) => {
+                            self.res.names.insert(callee.span, id);
+                        }
+                        None => self.diagnostics.push(
+                            Diagnostic::error(format!("cannot find function `{}`", name))
+                                .with_code(diag::UNDEFINED_FUNCTION)
+                                .with_label(callee.span, "not found"),
+                        ),
+                    },
+                    // `Struct.function(...)` calls an associated function and `Enum.Variant(...)` builds a
+                    // variant, unless a local has the same name
+                    ExprKind::Field { expr: object, .. } => match self.owner(object) {
+                        Some(id) => {
+                            self.res.names.insert(object.span, id);
+                        }
+                        None => self.resolve_expr(object),
+                    },
+                    _ => self.resolve_expr(callee),
+                }
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+            // fields depend on the type of what they are accessed on, which the type checker knows.
+            // `Enum.Variant` is a variant without fields
+            ExprKind::Field { expr: object, .. } => match self.owner(object) {
+                Some(id) if self.res.decls[id].kind == DeclKind::Enum => {
+                    self.res.names.insert(object.span, id);
+                }
+                _ => self.resolve_expr(object),
+            },
+            ExprKind::Index { expr, index } => {
+                self.resolve_expr(expr);
+                self.resolve_expr(index);
+            }
+            ExprKind::Range { start, end, .. } => {
+                self.resolve_expr(start);
+                self.resolve_expr(end);
+            }
+            ExprKind::StructLit { name, variant, fields } => {
+                if variant.is_some() {
+                    self.resolve_enum_name(name);
+                } else {
+                    self.resolve_struct_name(name);
+                }
+                for (_, value) in fields {
+                    self.resolve_expr(value);
+                }
+            }
+            ExprKind::Match { scrutinee, arms } => self.resolve_arms(scrutinee, arms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::program;
+
+    // the codes of the errors resolving `source` reports
+    fn errors(source: &str) -> Vec<&'static str> {
+        let (_, diagnostics) = resolve(&program(source));
+        diagnostics.iter().filter(|d| d.is_error()).filter_map(|d| d.code).collect()
+    }
+
+    #[test]
+    fn functions_cannot_redefine_builtins() {
+        let source = "fn len(list<int> xs) -> int { return 0; } fn main() { print(len([1])); }";
+        assert_eq!(errors(source), vec![diag::DUPLICATE_DEFINITION]);
+    }
+
+    // the number of warnings resolving `source` reports
+    fn warnings(source: &str) -> usize {
+        let (_, diagnostics) = resolve(&program(source));
+        diagnostics.iter().filter(|d| !d.is_error()).count()
+    }
+
+    #[test]
+    fn undefined_names_are_reported() {
+        assert_eq!(errors("fn main() { print(x); }"), vec![diag::UNDEFINED_VARIABLE]);
+        assert_eq!(errors("fn main() { f(); }"), vec![diag::UNDEFINED_FUNCTION]);
+        assert_eq!(errors("fn main() { list<T> xs = []; }"), vec![diag::UNKNOWN_TYPE]);
+        assert_eq!(errors("fn main() { print(S { }); }"), vec![diag::UNKNOWN_STRUCT]);
+        // functions are called rather than read
+        assert_eq!(errors("fn main() { print(main); }"), vec![diag::UNDEFINED_VARIABLE]);
+    }
+
+    #[test]
+    fn locals_are_only_in_scope_after_their_declaration_and_inside_their_block() {
+        assert_eq!(errors("fn main() { print_int(x); int x = 1; }"), vec![diag::UNDEFINED_VARIABLE]);
+        assert_eq!(errors("fn main() { { int x = 1; } print_int(x); }"), vec![diag::UNDEFINED_VARIABLE]);
+        assert_eq!(errors("fn main() { for i in 0..2 { } print_int(i); }"), vec![diag::UNDEFINED_VARIABLE]);
+        assert_eq!(errors("fn f() { print_int(x); } fn main() { int x = 1; f(); }"), vec![diag::UNDEFINED_VARIABLE]);
+        // items can be used before they are declared
+        assert!(errors("fn main() { f(S { x: 1 }); } fn f(S s) { } struct S { int x; }").is_empty());
+    }
+
+    #[test]
+    fn names_can_only_be_declared_once_per_scope() {
+        assert_eq!(errors("fn f() { } fn f() { } fn main() { }"), vec![diag::DUPLICATE_DEFINITION]);
+        assert_eq!(errors("fn f(int a, int a) { } fn main() { }"), vec![diag::DUPLICATE_DEFINITION]);
+        assert_eq!(errors("fn main() { int x = 1; int x = 2; }"), vec![diag::DUPLICATE_DEFINITION]);
+        assert_eq!(errors("struct S { int x; int x; } fn main() { }"), vec![diag::DUPLICATE_DEFINITION]);
+        assert_eq!(errors("struct S { } fn S() { } fn main() { }"), vec![diag::DUPLICATE_DEFINITION]);
+    }
+
+    #[test]
+    fn shadowing_an_outer_local_is_a_warning() {
+        assert_eq!(warnings("fn main() { int x = 1; { int x = 2; print_int(x); } print_int(x); }"), 1);
+        assert_eq!(warnings("fn f(int a) { for a in 0..2 { print_int(a); } }"), 1);
+        // a redeclaration in the same scope is an error instead
+        assert_eq!(warnings("fn main() { int x = 1; int x = 2; }"), 0);
+        assert_eq!(warnings("fn f(int a) { } fn g(int a) { }"), 0);
+    }
+
+    #[test]
+    fn names_resolve_to_the_nearest_declaration() {
+        let source = "fn main() { int x = 1; { int x = 2; print_int(x); } print_int(x); }";
+        let (res, _) = resolve(&program(source));
+        // the declaration a name at byte `offset` of the source refers to
+        let decl = |offset: usize| {
+            let (_, &id) = res.names.iter().find(|(span, _)| span.offset == offset).unwrap();
+            id
+        };
+        let at = |pattern: &str| source.match_indices(pattern).map(|(i, _)| i).collect::<Vec<_>>();
+        let (decls, uses) = (at("x ="), at("x)"));
+        assert_eq!(decl(uses[0]), decl(decls[1]));
+        assert_eq!(decl(uses[1]), decl(decls[0]));
+        assert_ne!(decl(decls[0]), decl(decls[1]));
+        assert_eq!(res.decls[decl(uses[0])].kind, DeclKind::Local);
+    }
+}
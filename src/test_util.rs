@@ -0,0 +1,24 @@
+// the setup the passes' unit tests share: turning source text into tokens, a program or its result
+
+use crate::ast::Program;
+use crate::interp::{self, RuntimeError, Value};
+use crate::lex::{Lexer, SpannedToken};
+use crate::{lower, parse, resolve, typeck};
+
+// the tokens of `source`, which must have no lexer errors
+pub fn tokens(source: &str) -> Vec<SpannedToken> {
+    Lexer::new(source.to_string()).collect::<Result<Vec<_>, _>>().unwrap()
+}
+
+// `source` parsed, which must have no syntax errors
+pub fn program(source: &str) -> Program {
+    parse::parse(tokens(source)).unwrap()
+}
+
+// the value `main` returns when `source` is checked, lowered and run
+pub fn run(source: &str) -> Result<Value, RuntimeError> {
+    let program = program(source);
+    let (res, _) = resolve::resolve(&program);
+    let (promoted, _) = typeck::check(&program, &res);
+    interp::run(&lower::lower(program), &promoted)
+}
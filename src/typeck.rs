@@ -0,0 +1,1002 @@
+// the static type checker, run after `resolve` and `validate` even when they found mistakes, so that
+// every error is reported at once. names that didn't resolve and types that don't exist are checked
+// as `Ty::Unknown`, which fits anywhere, rather than reported again
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::ast::*;
+use crate::diag::{self, Diagnostic};
+use crate::lex::Span;
+use crate::resolve::{DeclId, DeclKind, Resolution};
+
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub enum Ty {
+    Int,
+    Float,
+    Bool,
+    String,
+    List(Box<Ty>),
+    Map(Box<Ty>, Box<Ty>),
+    // `start..end` and `start..=end`, which can only be iterated over
+    Range,
+    Null,
+    Struct(String),
+    Enum(String),
+    // the type of something already reported as wrong, which is compatible with everything
+    // so that one mistake doesn't cascade into many
+    Unknown,
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Int => write!(f, "int"),
+            Ty::Float => write!(f, "float"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::String => write!(f, "string"),
+            Ty::List(item) => write!(f, "list<{}>", item),
+            Ty::Map(key, value) => write!(f, "map<{}, {}>", key, value),
+            Ty::Range => write!(f, "range"),
+            Ty::Null => write!(f, "null"),
+            Ty::Struct(name) | Ty::Enum(name) => write!(f, "{}", name),
+            Ty::Unknown => write!(f, "{{unknown}}"),
+        }
+    }
+}
+
+impl Ty {
+    // an int is promoted to a float wherever a float is expected, but never the other way round
+    fn accepts(&self, other: &Ty) -> bool {
+        (*self == Ty::Float && *other == Ty::Int) || self.same(other)
+    }
+
+    // equal, other than for unknown types. list elements are stored as they are, so a list of
+    // ints isn't a list of floats
+    fn same(&self, other: &Ty) -> bool {
+        match (self, other) {
+            (Ty::Unknown, _) | (_, Ty::Unknown) => true,
+            (Ty::List(a), Ty::List(b)) => a.same(b),
+            (Ty::Map(a, x), Ty::Map(b, y)) => a.same(b) && x.same(y),
+            (a, b) => a == b,
+        }
+    }
+
+    // maps are keyed by the types that compare exactly
+    fn is_key(&self) -> bool {
+        matches!(self, Ty::Int | Ty::String | Ty::Bool | Ty::Unknown)
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(self, Ty::Int | Ty::Float | Ty::Unknown)
+    }
+}
+
+// the int expressions whose values are used as floats, by their spans, which the interpreter converts
+// as it evaluates them. `float x = 1`, `push(floats, 1)` and `return 1` in a function returning a
+// float all store a float
+pub type Promotions = HashSet<Span>;
+
+pub fn check(program: &Program, res: &Resolution) -> (Promotions, Vec<Diagnostic>) {
+    let mut checker = Checker {
+        res,
+        structs: HashMap::new(),
+        enums: HashMap::new(),
+        functions: HashMap::new(),
+        locals: HashMap::new(),
+        ret: Ty::Null,
+        promoted: HashSet::new(),
+        diagnostics: Vec::new(),
+    };
+
+    for item in &program.items {
+        match item {
+            Item::Fn(function) => {
+                if let Some(id) = res.decl_at(function.name.span) {
+                    checker.functions.insert(id, function);
+                }
+            }
+            Item::Struct(decl) => {
+                checker.structs.entry(&decl.name.name).or_insert(decl);
+            }
+            Item::Enum(decl) => {
+                checker.enums.entry(&decl.name.name).or_insert(decl);
+            }
+        }
+    }
+
+    for item in &program.items {
+        match item {
+            Item::Fn(function) => checker.check_fn(function, None),
+            Item::Struct(decl) => {
+                for method in &decl.methods {
+                    checker.check_fn(method, Some(&decl.name.name));
+                }
+            }
+            Item::Enum(_) => {}
+        }
+    }
+
+    (checker.promoted, checker.diagnostics)
+}
+
+struct Checker<'a> {
+    res: &'a Resolution,
+    structs: HashMap<&'a str, &'a StructDecl>,
+    enums: HashMap<&'a str, &'a EnumDecl>,
+    functions: HashMap<DeclId, &'a FnDecl>,
+    // the types of the parameters and locals seen so far, by the declaration names resolve to
+    locals: HashMap<DeclId, Ty>,
+    // the return type of the function being checked
+    ret: Ty,
+    promoted: Promotions,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn mismatch(&mut self, span: Span, expected: &Ty, found: &Ty, context: &str) {
+        self.diagnostics.push(mismatched(span, expected, found, context));
+    }
+
+    // report a mismatch unless `found` fits where `expected` is needed, noting an int that has to
+    // become a float to fit
+    fn expect_ty(&mut self, span: Span, expected: &Ty, found: &Ty, context: &str) {
+        if *expected == Ty::Float && *found == Ty::Int {
+            self.promoted.insert(span);
+        } else if !expected.accepts(found) {
+            self.mismatch(span, expected, found, context);
+        }
+    }
+
+    // unknown types are reported by the resolver, and checked here as if they were fine
+    fn resolve_type(&self, ty: &TypeExpr) -> Ty {
+        match &ty.kind {
+            Type::Int => Ty::Int,
+            Type::String => Ty::String,
+            Type::Float => Ty::Float,
+            Type::Bool => Ty::Bool,
+            Type::List(item) => Ty::List(Box::new(self.resolve_type(item))),
+            Type::Map(key, value) => Ty::Map(Box::new(self.resolve_type(key)), Box::new(self.resolve_type(value))),
+            Type::Null => Ty::Null,
+            Type::Named(name) => match self.res.decl_at(ty.span).map(|id| self.res.decls[id].kind) {
+                Some(DeclKind::Struct) => Ty::Struct(name.clone()),
+                Some(DeclKind::Enum) => Ty::Enum(name.clone()),
+                _ => Ty::Unknown,
+            },
+        }
+    }
+
+    fn declare(&mut self, name: &Ident, ty: Ty) {
+        if let Some(id) = self.res.decl_at(name.span) {
+            self.locals.insert(id, ty);
+        }
+    }
+
+    // `owner` is the struct a method belongs to, which is the type of its `self`
+    fn check_fn(&mut self, function: &FnDecl, owner: Option<&str>) {
+        if let (Some(span), Some(owner)) = (function.receiver, owner) {
+            if let Some(id) = self.res.decl_at(span) {
+                self.locals.insert(id, Ty::Struct(owner.to_string()));
+            }
+        }
+        for param in &function.params {
+            let ty = self.resolve_type(&param.ty);
+            self.declare(&param.name, ty);
+        }
+        self.ret = self.resolve_type(&function.ret);
+
+        self.check_block(&function.body);
+
+        if self.ret != Ty::Null && self.ret != Ty::Unknown && !returns(&function.body) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("`{}` might not return a value", function.name.name))
+                    .with_code(diag::MISSING_RETURN)
+                    .with_label(function.body.span, format!("can reach the end without returning `{}`", self.ret))
+                    .with_secondary(function.ret.span, "return type declared here"),
+            );
+        }
+    }
+
+    fn check_block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            self.check_stmt(stmt);
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let { ty, name, value } => {
+                let found = value.as_ref().map(|value| (value.span, self.check_expr(value)));
+                let declared = match (ty, &found) {
+                    (Some(ty), _) => self.resolve_type(ty),
+                    (None, Some((_, found))) => found.clone(),
+                    (None, None) => Ty::Unknown,
+                };
+                match found {
+                    Some((span, found)) => {
+                        let context = format!("`{}` is declared as `{}`", name.name, declared);
+                        self.expect_ty(span, &declared, &found, &context);
+                    }
+                    // without a value the variable would start out null, which its type doesn't allow
+                    None if declared != Ty::Null && declared != Ty::Unknown => {
+                        self.diagnostics.push(
+                            Diagnostic::error(format!("`{}` is declared without a value", name.name))
+                                .with_code(diag::UNINITIALIZED_VARIABLE)
+                                .with_label(stmt.span, format!("a `{}` needs a value to start with", declared))
+                                .with_help(format!("give it one: `{} {} = ...;`", declared, name.name)),
+                        );
+                    }
+                    None => {}
+                }
+                self.declare(name, declared);
+            }
+            StmtKind::Assign { target, value } => {
+                if !target.is_place() {
+                    self.diagnostics.push(
+                        Diagnostic::error("invalid left side of assignment")
+                            .with_code(diag::UNSUPPORTED_OPERATION)
+                            .with_label(target.span, "cannot be assigned to")
+                            .with_note("only variables, and fields and elements of them, can be assigned to"),
+                    );
+                }
+                let expected = self.check_expr(target);
+                let found = self.check_expr(value);
+                let context = "the value must match the type of what it is assigned to";
+                self.expect_ty(value.span, &expected, &found, context);
+            }
+            StmtKind::If {
+                branches,
+                otherwise,
+            } => {
+                for (cond, body) in branches {
+                    self.check_condition(cond);
+                    self.check_block(body);
+                }
+                if let Some(body) = otherwise {
+                    self.check_block(body);
+                }
+            }
+            StmtKind::While { cond, body } => {
+                self.check_condition(cond);
+                self.check_block(body);
+            }
+            StmtKind::For { var, iter, body } => {
+                let item = match self.check_expr(iter) {
+                    // a range is only lowered to a counting loop when its ends are written out
+                    Ty::Range if !matches!(iter.kind, ExprKind::Range { .. }) => {
+                        self.diagnostics.push(
+                            Diagnostic::error("cannot iterate over a range that isn't written out")
+                                .with_code(diag::UNSUPPORTED_OPERATION)
+                                .with_label(iter.span, "this range is computed")
+                                .with_help("write the range after `in`, as `for i in start..end`"),
+                        );
+                        Ty::Int
+                    }
+                    Ty::Range => Ty::Int,
+                    Ty::List(item) => *item,
+                    Ty::Unknown => Ty::Unknown,
+                    other => {
+                        self.unsupported(iter.span, format!("cannot iterate over `{}`", other));
+                        Ty::Unknown
+                    }
+                };
+                self.declare(var, item);
+                self.check_block(body);
+            }
+            StmtKind::Match { scrutinee, arms } => {
+                self.check_match(scrutinee, arms, false);
+            }
+            StmtKind::Return(value) => {
+                let ret = self.ret.clone();
+                match value {
+                    Some(value) => {
+                        let found = self.check_expr(value);
+                        let context = format!("the function returns `{}`", ret);
+                        self.expect_ty(value.span, &ret, &found, &context);
+                    }
+                    None if ret != Ty::Null && ret != Ty::Unknown => {
+                        let context = format!("the function returns `{}`", ret);
+                        self.mismatch(stmt.span, &ret, &Ty::Null, &context);
+                    }
+                    None => {}
+                }
+            }
+            StmtKind::Break | StmtKind::Continue => {}
+            StmtKind::Block(body) => self.check_block(body),
+            StmtKind::Expr(expr) => {
+                self.check_expr(expr);
+            }
+        }
+    }
+
+    fn check_condition(&mut self, cond: &Expr) {
+        let found = self.check_expr(cond);
+        self.expect_ty(cond.span, &Ty::Bool, &found, "conditions must be `bool`");
+    }
+
+    fn check_expr(&mut self, expr: &Expr) -> Ty {
+        match &expr.kind {
+            ExprKind::Int(_) => Ty::Int,
+            ExprKind::Float(_) => Ty::Float,
+            ExprKind::Bool(_) => Ty::Bool,
+            ExprKind::Str(_) => Ty::String,
+            ExprKind::Null => Ty::Null,
+            // undefined names are left to the resolver, and checked as if they were fine
+            ExprKind::Ident(_) => self
+                .res
+                .decl_at(expr.span)
+                .and_then(|id| self.locals.get(&id))
+                .cloned()
+                .unwrap_or(Ty::Unknown),
+            ExprKind::List(items) => {
+                // the first element decides the type of the rest, and `[]` fits any list
+                let mut item_ty = Ty::Unknown;
+                for item in items {
+                    let found = self.check_expr(item);
+                    if item_ty == Ty::Unknown {
+                        item_ty = found;
+                    } else if !item_ty.same(&found) {
+                        let context = "the elements of a list must all have the same type";
+                        self.mismatch(item.span, &item_ty, &found, context);
+                    }
+                }
+                Ty::List(Box::new(item_ty))
+            }
+            ExprKind::Map(entries) => {
+                // like lists, the first entry decides the types of the rest
+                let (mut key_ty, mut value_ty) = (Ty::Unknown, Ty::Unknown);
+                for (key, value) in entries {
+                    let found = self.check_expr(key);
+                    if !found.is_key() {
+                        self.unsupported(key.span, format!("`{}` can't be used as a map key", found));
+                    } else if key_ty == Ty::Unknown {
+                        key_ty = found;
+                    } else if !key_ty.same(&found) {
+                        self.mismatch(key.span, &key_ty, &found, "the keys of a map must all have the same type");
+                    }
+                    let found = self.check_expr(value);
+                    if value_ty == Ty::Unknown {
+                        value_ty = found;
+                    } else if !value_ty.same(&found) {
+                        let context = "the values of a map must all have the same type";
+                        self.mismatch(value.span, &value_ty, &found, context);
+                    }
+                }
+                Ty::Map(Box::new(key_ty), Box::new(value_ty))
+            }
+            ExprKind::Unary { op, expr: operand } => {
+                let ty = self.check_expr(operand);
+                let ok = match op {
+                    UnaryOp::Neg => ty.is_numeric(),
+                    UnaryOp::Not => Ty::Bool.accepts(&ty),
+                };
+                if !ok {
+                    self.unsupported(expr.span, format!("cannot apply `{}` to `{}`", op, ty));
+                    return Ty::Unknown;
+                }
+                ty
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let lhs_ty = self.check_expr(lhs);
+                let rhs_ty = self.check_expr(rhs);
+                self.check_binary(expr.span, *op, lhs_ty, rhs_ty)
+            }
+            ExprKind::Call { callee, args } => self.check_call(expr.span, callee, args),
+            ExprKind::Field { expr: object, field } => {
+                // `Enum.Variant` for a variant without fields
+                if let Some(decl) = self.enum_named(object) {
+                    self.variant(decl, field, expr.span, |fields| matches!(fields, VariantFields::Unit));
+                    return Ty::Enum(decl.name.name.clone());
+                }
+                let ty = self.check_expr(object);
+                self.field_type(&ty, field)
+            }
+            ExprKind::Index { expr: object, index } => self.check_index(object, index).1,
+            ExprKind::Range { start, end, .. } => {
+                for bound in [start, end] {
+                    let found = self.check_expr(bound);
+                    self.expect_ty(bound.span, &Ty::Int, &found, "the bounds of a range must be `int`");
+                }
+                Ty::Range
+            }
+            ExprKind::StructLit {
+                name,
+                variant: None,
+                fields,
+            } => self.check_struct_literal(name, fields),
+            ExprKind::StructLit {
+                name,
+                variant: Some(variant),
+                fields,
+            } => {
+                let decl = self.enums.get(name.name.as_str()).copied();
+                let fits = |fields: &VariantFields| matches!(fields, VariantFields::Struct(_));
+                let declared = match decl.and_then(|decl| self.variant(decl, variant, expr.span, fits)) {
+                    Some(Variant {
+                        fields: VariantFields::Struct(declared),
+                        ..
+                    }) => &declared[..],
+                    _ => &[],
+                };
+                // unknown and missing fields are reported by the validator, as for structs
+                for (field, value) in fields {
+                    let found = self.check_expr(value);
+                    let Some(declared) = declared.iter().find(|f| f.name.name == field.name) else {
+                        continue;
+                    };
+                    let expected = self.resolve_type(&declared.ty);
+                    let context = format!("`{}` is declared as `{}`", field.name, expected);
+                    self.expect_ty(value.span, &expected, &found, &context);
+                }
+                match decl {
+                    Some(decl) => Ty::Enum(decl.name.name.clone()),
+                    None => Ty::Unknown,
+                }
+            }
+            ExprKind::Match { scrutinee, arms } => self.check_match(scrutinee, arms, true),
+        }
+    }
+
+    // the enum that `object` names in `Enum.Variant`, unless it is a value
+    fn enum_named(&self, object: &Expr) -> Option<&'a EnumDecl> {
+        let ExprKind::Ident(name) = &object.kind else {
+            return None;
+        };
+        let id = self.res.decl_at(object.span)?;
+        if self.res.decls[id].kind != DeclKind::Enum {
+            return None;
+        }
+        self.enums.get(name.as_str()).copied()
+    }
+
+    // the variant `name` of `decl`, built at `span` in a way that `fits` the fields it must have
+    fn variant(
+        &mut self,
+        decl: &'a EnumDecl,
+        name: &Ident,
+        span: Span,
+        fits: impl Fn(&VariantFields) -> bool,
+    ) -> Option<&'a Variant> {
+        let Some(variant) = decl.variants.iter().find(|v| v.name.name == name.name) else {
+            self.diagnostics.push(diag::unknown_variant(decl, name));
+            return None;
+        };
+        if !fits(&variant.fields) {
+            self.diagnostics.push(diag::wrong_shape(decl, variant, span));
+            return None;
+        }
+        Some(variant)
+    }
+
+    // the type of a `match`, which its first arm decides when it is used as a `value`, so an int arm
+    // after a float one becomes a float. the arms of a statement can have any types, since their
+    // values are thrown away
+    fn check_match(&mut self, scrutinee: &Expr, arms: &[Arm], value: bool) -> Ty {
+        let ty = self.check_expr(scrutinee);
+        let mut result = None;
+        for arm in arms {
+            self.check_pattern(&arm.pattern, &ty);
+            let expr = match &arm.body {
+                ArmBody::Expr(expr) => expr,
+                ArmBody::Block(body) => {
+                    self.check_block(body);
+                    continue;
+                }
+            };
+            let found = self.check_expr(expr);
+            match &result {
+                Some(expected) if value => {
+                    let context = "the arms of a `match` must all have the same type";
+                    self.expect_ty(expr.span, expected, &found, context);
+                }
+                Some(_) => {}
+                None => result = Some(found),
+            }
+        }
+        result.unwrap_or(Ty::Unknown)
+    }
+
+    // declare what `pattern` binds, given the type of the value it is matched against. patterns
+    // for the wrong fields or variants are reported by the validator
+    fn check_pattern(&mut self, pattern: &Pattern, ty: &Ty) {
+        let context = format!("the value being matched is `{}`", ty);
+        match &pattern.kind {
+            PatternKind::Wildcard => {}
+            PatternKind::Binding(name) => self.declare(name, ty.clone()),
+            PatternKind::Literal(expr) => {
+                // literals are compared with `==`, which works between ints and floats
+                let found = self.check_expr(expr);
+                let numbers = ty.is_numeric() && found.is_numeric();
+                if !numbers && !ty.same(&found) {
+                    self.mismatch(pattern.span, ty, &found, &context);
+                }
+            }
+            PatternKind::Variant { name, variant, fields } => {
+                let decl = self.enums.get(name.name.as_str()).copied();
+                if let Some(decl) = decl {
+                    self.expect_ty(pattern.span, ty, &Ty::Enum(decl.name.name.clone()), &context);
+                }
+                let declared = decl.and_then(|decl| decl.variants.iter().find(|v| v.name.name == variant.name));
+                match (fields, declared.map(|v| &v.fields)) {
+                    (PatternFields::Unit, _) => {}
+                    (PatternFields::Tuple(patterns), Some(VariantFields::Tuple(types))) => {
+                        let types: Vec<Ty> = types.iter().map(|ty| self.resolve_type(ty)).collect();
+                        for (i, pattern) in patterns.iter().enumerate() {
+                            self.check_pattern(pattern, types.get(i).unwrap_or(&Ty::Unknown));
+                        }
+                    }
+                    (PatternFields::Tuple(patterns), _) => {
+                        for pattern in patterns {
+                            self.check_pattern(pattern, &Ty::Unknown);
+                        }
+                    }
+                    (PatternFields::Struct(fields), Some(VariantFields::Struct(declared))) => {
+                        self.check_field_patterns(fields, declared);
+                    }
+                    (PatternFields::Struct(fields), _) => self.check_field_patterns(fields, &[]),
+                }
+            }
+            PatternKind::Struct { name, fields } => {
+                let decl = self.structs.get(name.name.as_str()).copied();
+                if let Some(decl) = decl {
+                    self.expect_ty(pattern.span, ty, &Ty::Struct(decl.name.name.clone()), &context);
+                }
+                self.check_field_patterns(fields, decl.map_or(&[], |decl| &decl.fields));
+            }
+        }
+    }
+
+    fn check_field_patterns(&mut self, fields: &[(Ident, Pattern)], declared: &[Field]) {
+        for (field, pattern) in fields {
+            let ty = match declared.iter().find(|f| f.name.name == field.name) {
+                Some(declared) => self.resolve_type(&declared.ty),
+                None => Ty::Unknown,
+            };
+            self.check_pattern(pattern, &ty);
+        }
+    }
+
+    // the type of what is indexed, and of the element or slice taken from it
+    fn check_index(&mut self, object: &Expr, index: &Expr) -> (Ty, Ty) {
+        let ty = self.check_expr(object);
+        let index_ty = self.check_expr(index);
+        let item = match &ty {
+            Ty::List(item) => (**item).clone(),
+            Ty::Map(key, value) => {
+                self.expect_ty(index.span, key, &index_ty, &format!("the map is keyed by `{}`", key));
+                let value = (**value).clone();
+                return (ty, value);
+            }
+            Ty::Unknown => Ty::Unknown,
+            other => {
+                self.unsupported(object.span, format!("cannot index into `{}`", other));
+                return (ty, Ty::Unknown);
+            }
+        };
+        // indexing by a range takes a slice
+        if index_ty == Ty::Range {
+            return (ty, Ty::List(Box::new(item)));
+        }
+        let context = "lists are indexed by `int`, or sliced by a range";
+        self.expect_ty(index.span, &Ty::Int, &index_ty, context);
+        (ty, item)
+    }
+
+    fn unsupported(&mut self, span: Span, message: String) {
+        self.diagnostics.push(
+            Diagnostic::error(message)
+                .with_code(diag::UNSUPPORTED_OPERATION)
+                .with_label(span, "not supported for this type"),
+        );
+    }
+
+    fn check_binary(&mut self, span: Span, op: BinaryOp, lhs: Ty, rhs: Ty) -> Ty {
+        if lhs == Ty::Unknown || rhs == Ty::Unknown {
+            return match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => Ty::Unknown,
+                _ => Ty::Bool,
+            };
+        }
+
+        // an int mixed with a float is promoted, so numbers always work together. strings can be
+        // concatenated with `+` and are ordered by comparing them lexicographically
+        let numeric = lhs.is_numeric() && rhs.is_numeric();
+        let strings = lhs == Ty::String && rhs == Ty::String;
+        let lists = matches!((&lhs, &rhs), (Ty::List(_), Ty::List(_))) && lhs.same(&rhs);
+        let ok = match op {
+            BinaryOp::Add => numeric || strings || lists,
+            BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => numeric,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => numeric || strings,
+            BinaryOp::Eq | BinaryOp::Ne => numeric || lhs.same(&rhs),
+            BinaryOp::And | BinaryOp::Or => lhs == Ty::Bool && rhs == Ty::Bool,
+        };
+        if !ok {
+            self.diagnostics.push(
+                Diagnostic::error(format!("cannot apply `{}` to `{}` and `{}`", op, lhs, rhs))
+                    .with_code(diag::UNSUPPORTED_OPERATION)
+                    .with_label(span, "operands have incompatible types"),
+            );
+            return Ty::Unknown;
+        }
+
+        match op {
+            // `[] + xs` has the type of `xs`
+            BinaryOp::Add if lists && lhs == Ty::List(Box::new(Ty::Unknown)) => rhs,
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div if numeric && lhs != rhs => Ty::Float,
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => lhs,
+            _ => Ty::Bool,
+        }
+    }
+
+    fn check_call(&mut self, span: Span, callee: &Expr, args: &[Expr]) -> Ty {
+        let arg_tys: Vec<Ty> = args.iter().map(|arg| self.check_expr(arg)).collect();
+
+        let name = match &callee.kind {
+            ExprKind::Ident(name) => name,
+            ExprKind::Field { expr: object, field } => {
+                // `Enum.Variant(...)` for a variant with unnamed fields
+                if let Some(decl) = self.enum_named(object) {
+                    let fits = |fields: &VariantFields| matches!(fields, VariantFields::Tuple(_));
+                    let ty = Ty::Enum(decl.name.name.clone());
+                    let params = match self.variant(decl, field, span, fits) {
+                        Some(Variant {
+                            fields: VariantFields::Tuple(types),
+                            ..
+                        }) => types.iter().map(|ty| self.resolve_type(ty)).collect(),
+                        _ => return ty,
+                    };
+                    let name = format!("{}.{}", decl.name.name, field.name);
+                    return self.check_args(span, &name, args, &arg_tys, (params, ty, Some(field.span)));
+                }
+                let Some(method) = self.method(object, field) else {
+                    return Ty::Unknown;
+                };
+                let params = method.params.iter().map(|p| self.resolve_type(&p.ty)).collect();
+                let ret = self.resolve_type(&method.ret);
+                return self.check_args(span, &field.name, args, &arg_tys, (params, ret, Some(method.name.span)));
+            }
+            _ => {
+                self.unsupported(callee.span, "only functions and methods can be called".to_string());
+                return Ty::Unknown;
+            }
+        };
+        let Some(id) = self.res.decl_at(callee.span) else {
+            return Ty::Unknown;
+        };
+
+        // the built-ins: print takes anything, print_int takes one int and to_int truncates a float
+        let signature = match self.res.decls[id].kind {
+            DeclKind::Builtin => match name.as_str() {
+                "print_int" => (vec![Ty::Int], Ty::Null, None),
+                "to_int" => (vec![Ty::Float], Ty::Int, None),
+                "print" => return Ty::Null,
+                _ => match self.collection_signature(name, args, &arg_tys) {
+                    Some((params, ret)) => (params, ret, None),
+                    None => return Ty::Unknown,
+                },
+            },
+            _ => {
+                let function = self.functions[&id];
+                let params = function.params.iter().map(|p| self.resolve_type(&p.ty)).collect();
+                (params, self.resolve_type(&function.ret), Some(function.name.span))
+            }
+        };
+        self.check_args(span, name, args, &arg_tys, signature)
+    }
+
+    // check the arguments of a call to `name` against its parameter types, returning the type of
+    // the call. the signature also has where `name` is declared, unless it is a built-in
+    fn check_args(
+        &mut self,
+        span: Span,
+        name: &str,
+        args: &[Expr],
+        arg_tys: &[Ty],
+        (params, ret, decl_span): (Vec<Ty>, Ty, Option<Span>),
+    ) -> Ty {
+        if params.len() != args.len() {
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            let mut diag = Diagnostic::error(format!(
+                "`{}` takes {} argument{} but {} {} given",
+                name,
+                params.len(),
+                plural(params.len()),
+                args.len(),
+                if args.len() == 1 { "was" } else { "were" }
+            ))
+            .with_code(diag::WRONG_ARGUMENT_COUNT)
+            .with_label(span, format!("expected {} argument{}", params.len(), plural(params.len())));
+            if let Some(decl_span) = decl_span {
+                diag = diag.with_secondary(decl_span, format!("`{}` defined here", name));
+            }
+            self.diagnostics.push(diag);
+            return ret;
+        }
+
+        for ((arg, found), expected) in args.iter().zip(arg_tys).zip(&params) {
+            let context = format!("the argument to `{}` must be `{}`", name, expected);
+            self.expect_ty(arg.span, expected, found, &context);
+        }
+
+        ret
+    }
+
+    // the parameter and return types of a list or map built-in, which depend on the collection given
+    // as the first argument
+    fn collection_signature(&mut self, name: &str, args: &[Expr], arg_tys: &[Ty]) -> Option<(Vec<Ty>, Ty)> {
+        if matches!(name, "push" | "pop" | "insert" | "remove") && args.first().is_some_and(|arg| !arg.is_place()) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("`{}` changes the collection it is given", name))
+                    .with_code(diag::UNSUPPORTED_OPERATION)
+                    .with_label(args[0].span, "not a variable, field or element")
+                    .with_help("store it in a variable first"),
+            );
+        }
+
+        let collection = arg_tys.first().cloned().unwrap_or(Ty::Unknown);
+        let (mut params, ret) = match (&collection, name) {
+            (Ty::Unknown, _) => return Some((vec![Ty::Unknown; args.len().max(1)], Ty::Unknown)),
+            (Ty::List(_) | Ty::Map(..), "len") => (vec![], Ty::Int),
+            (Ty::List(item), "push") => (vec![(**item).clone()], Ty::Null),
+            (Ty::List(item), "pop") => (vec![], (**item).clone()),
+            (Ty::List(item), "insert") => (vec![Ty::Int, (**item).clone()], Ty::Null),
+            (Ty::List(item), "remove") => (vec![Ty::Int], (**item).clone()),
+            (Ty::List(item), "contains") => (vec![(**item).clone()], Ty::Bool),
+            (Ty::Map(key, value), "remove") => (vec![(**key).clone()], (**value).clone()),
+            (Ty::Map(key, _), "contains") => (vec![(**key).clone()], Ty::Bool),
+            (Ty::Map(key, _), "keys") => (vec![], Ty::List(key.clone())),
+            (Ty::Map(_, value), "values") => (vec![], Ty::List(value.clone())),
+            (other, _) => {
+                let wants = match name {
+                    "push" | "pop" | "insert" => "a list",
+                    "keys" | "values" => "a map",
+                    _ => "a list or map",
+                };
+                self.unsupported(args[0].span, format!("`{}` needs {}, found `{}`", name, wants, other));
+                return None;
+            }
+        };
+        params.insert(0, collection);
+        Some((params, ret))
+    }
+
+    // the method `value.name(...)` calls, or the associated function `Struct.name(...)` calls
+    fn method(&mut self, object: &Expr, name: &Ident) -> Option<&'a FnDecl> {
+        let on_struct = match &object.kind {
+            ExprKind::Ident(ident) => self
+                .res
+                .decl_at(object.span)
+                .filter(|&id| self.res.decls[id].kind == DeclKind::Struct)
+                .map(|_| ident.clone()),
+            _ => None,
+        };
+        let owner = match on_struct.clone().map(Ty::Struct).unwrap_or_else(|| self.check_expr(object)) {
+            Ty::Struct(owner) => owner,
+            Ty::Unknown => return None,
+            other => {
+                self.unsupported(name.span, format!("`{}` has no methods", other));
+                return None;
+            }
+        };
+
+        let decl = self.structs[owner.as_str()];
+        let Some(method) = decl.methods.iter().find(|m| m.name.name == name.name) else {
+            let mut diag = Diagnostic::error(format!("no method `{}` on `{}`", name.name, owner))
+                .with_code(diag::UNKNOWN_METHOD)
+                .with_label(name.span, "unknown method")
+                .with_secondary(decl.name.span, format!("`{}` declared here", owner));
+            if let Some(close) = diag::closest(&name.name, decl.methods.iter().map(|m| m.name.name.as_str())) {
+                diag = diag.with_help(format!("did you mean `{}`?", close));
+            }
+            self.diagnostics.push(diag);
+            return None;
+        };
+
+        let mismatch = match (&on_struct, method.receiver) {
+            (Some(_), Some(_)) => Some((
+                format!("`{}` takes `self`, so it is called on a value", name.name),
+                format!("call it on a `{}`, like `value.{}(...)`", owner, name.name),
+            )),
+            (None, None) => Some((
+                format!("`{}` doesn't take `self`, so it is called on the struct", name.name),
+                format!("call it as `{}.{}(...)`", owner, name.name),
+            )),
+            _ => None,
+        };
+        if let Some((message, help)) = mismatch {
+            self.diagnostics.push(
+                Diagnostic::error(message)
+                    .with_code(diag::UNSUPPORTED_OPERATION)
+                    .with_label(name.span, "called the wrong way")
+                    .with_secondary(method.name.span, format!("`{}` declared here", name.name))
+                    .with_help(help),
+            );
+        }
+        Some(method)
+    }
+
+    fn field_type(&mut self, ty: &Ty, field: &Ident) -> Ty {
+        match ty {
+            Ty::Unknown => Ty::Unknown,
+            Ty::Struct(name) => {
+                let decl = self.structs[name.as_str()];
+                match decl.fields.iter().find(|f| f.name.name == field.name) {
+                    Some(declared) => self.resolve_type(&declared.ty),
+                    None => {
+                        let (owner, declared_at) = (&decl.name.name, decl.name.span);
+                        let diag = diag::unknown_field(owner, declared_at, &decl.fields, &decl.methods, field);
+                        self.diagnostics.push(diag);
+                        Ty::Unknown
+                    }
+                }
+            }
+            other => {
+                self.unsupported(field.span, format!("`{}` has no fields", other));
+                Ty::Unknown
+            }
+        }
+    }
+
+    // unknown structs are reported by the resolver, and unknown and missing fields by the validator,
+    // so only the types are checked here
+    fn check_struct_literal(&mut self, name: &Ident, fields: &[(Ident, Expr)]) -> Ty {
+        let decl = self.structs.get(name.name.as_str()).copied();
+
+        for (field, value) in fields {
+            let found = self.check_expr(value);
+            let Some(declared) = decl.and_then(|d| d.fields.iter().find(|f| f.name.name == field.name)) else {
+                continue;
+            };
+            let expected = self.resolve_type(&declared.ty);
+            let context = format!("`{}.{}` is declared as `{}`", name.name, field.name, expected);
+            self.expect_ty(value.span, &expected, &found, &context);
+        }
+
+        match decl {
+            Some(_) => Ty::Struct(name.name.clone()),
+            None => Ty::Unknown,
+        }
+    }
+}
+
+fn mismatched(span: Span, expected: &Ty, found: &Ty, context: &str) -> Diagnostic {
+    Diagnostic::error("mismatched types")
+        .with_code(diag::MISMATCHED_TYPES)
+        .with_label(span, format!("expected `{}`, found `{}`", expected, found))
+        .with_note(context.to_string())
+}
+
+// whether every path through a block ends in a return
+fn returns(block: &Block) -> bool {
+    block.stmts.iter().any(|stmt| match &stmt.kind {
+        StmtKind::Return(_) => true,
+        StmtKind::Block(body) => returns(body),
+        // the validator makes sure every value is matched by some arm
+        StmtKind::Match { arms, .. } => {
+            !arms.is_empty() && arms.iter().all(|arm| matches!(&arm.body, ArmBody::Block(body) if returns(body)))
+        }
+        StmtKind::If {
+            branches,
+            otherwise: Some(otherwise),
+        } => branches.iter().all(|(_, body)| returns(body)) && returns(otherwise),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve;
+    use crate::test_util::program;
+
+    // the codes of the errors the type checker reports for `source`
+    fn errors(source: &str) -> Vec<&'static str> {
+        let program = program(source);
+        let (res, _) = resolve::resolve(&program);
+        check(&program, &res).1.iter().filter(|d| d.is_error()).filter_map(|d| d.code).collect()
+    }
+
+    #[test]
+    fn ints_are_promoted_where_floats_are_stored() {
+        let source = "fn main() {
+                list<float> xs = [];
+                push(xs, 1);
+                insert(xs, 0, 2);
+                xs[0] = 3;
+                map<string, float> m = {};
+                m[\"a\"] = 4;
+                print(contains(xs, 1));
+            }";
+        assert!(errors(source).is_empty());
+        let floats = "fn main() { list<int> xs = []; push(xs, 1.5); map<string, int> m = {}; m[\"a\"] = 2.5; }";
+        assert_eq!(errors(floats), vec![diag::MISMATCHED_TYPES; 2]);
+    }
+
+    #[test]
+    fn only_written_out_ranges_are_iterated() {
+        let source = "fn main() { for i in match true { true => 0..2, false => 1..3 } { print(i); } }";
+        assert_eq!(errors(source), vec![diag::UNSUPPORTED_OPERATION]);
+        assert!(errors("fn main() { for i in 0..=2 { print(i); } }").is_empty());
+    }
+
+    #[test]
+    fn variables_need_a_value() {
+        assert_eq!(errors("fn main() { int u; print_int(u); }"), vec![diag::UNINITIALIZED_VARIABLE]);
+        assert!(errors("fn main() { int u = 1; print_int(u); }").is_empty());
+    }
+
+    #[test]
+    fn operators_need_operands_they_work_on() {
+        let ok = "fn main() { print(1 + 1.5); print(\"a\" + \"b\"); print(\"a\" < \"b\"); print([1] + [2]); }";
+        assert!(errors(ok).is_empty());
+        assert!(errors("fn main() { print(1 == 1.0); print(!(1 < 2)); }").is_empty());
+        let unsupported = [
+            "fn main() { print(\"a\" - \"b\"); }",
+            "fn main() { print(1 + \"a\"); }",
+            "fn main() { print(true && 1); }",
+            "fn main() { print([1] + [\"a\"]); }",
+            "fn main() { print(true < false); }",
+        ];
+        for source in unsupported {
+            assert_eq!(errors(source), vec![diag::UNSUPPORTED_OPERATION], "{}", source);
+        }
+        assert_eq!(errors("fn main() { int x = 1.5; bool b = 1; }"), vec![diag::MISMATCHED_TYPES; 2]);
+        assert_eq!(errors("fn main() { if 1 { } while \"a\" { } }"), vec![diag::MISMATCHED_TYPES; 2]);
+    }
+
+    #[test]
+    fn calls_need_the_right_number_of_arguments() {
+        let source = "fn f(int a) {} fn main() { f(); f(1, 2); print(len([1], [2])); f(1); }";
+        assert_eq!(errors(source), vec![diag::WRONG_ARGUMENT_COUNT; 3]);
+        assert_eq!(errors("fn f(int a) {} fn main() { f(\"a\"); }"), vec![diag::MISMATCHED_TYPES]);
+    }
+
+    #[test]
+    fn functions_with_a_return_type_must_return() {
+        let missing = [
+            "fn f(int a) -> int { if a > 0 { return 1; } }",
+            "fn f(int a) -> int { if a > 0 { return 1; } elif a < 0 { return 2; } }",
+            "fn f(int a) -> int { while a > 0 { return 1; } }",
+            "fn f(int a) -> int { print(a); }",
+        ];
+        for source in missing {
+            assert_eq!(errors(source), vec![diag::MISSING_RETURN], "{}", source);
+        }
+        let returning = [
+            "fn f(int a) -> int { if a > 0 { return 1; } else { return 2; } }",
+            "fn f(int a) -> int { { return a; } }",
+            "fn f(bool b) -> int { match b { true => { return 1; }, false => { return 2; } } }",
+            "fn f(int a) { print(a); }",
+        ];
+        for source in returning {
+            assert!(errors(source).is_empty(), "{}", source);
+        }
+        assert_eq!(errors("fn f() -> int { return \"a\"; }"), vec![diag::MISMATCHED_TYPES]);
+    }
+
+    #[test]
+    fn misspelled_names_get_a_suggestion() {
+        let help = |body: &str| {
+            let source = format!(
+                "struct P {{ int length; int width; fn area(self) -> int {{ return self.length * self.width; }} }}
+                struct H {{ P p; }}
+                enum Shape {{ Circle, Square }}
+                fn main() {{ P p = P {{ length: 1, width: 2 }}; H h = H {{ p: p }}; {} }}",
+                body
+            );
+            let program = program(&source);
+            let (res, _) = resolve::resolve(&program);
+            let (_, diagnostics) = check(&program, &res);
+            diagnostics.iter().flat_map(|d| d.help.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(help("print_int(p.lenght);"), vec!["did you mean `length`?"]);
+        assert_eq!(help("p.widht = 3;"), vec!["did you mean `width`?"]);
+        assert_eq!(help("h.p.lenth = h.p.width;"), vec!["did you mean `length`?"]);
+        assert_eq!(help("print_int(p.are());"), vec!["did you mean `area`?"]);
+        assert_eq!(help("print(Shape.Cirle);"), vec!["did you mean `Circle`?"]);
+        // with nothing close, the names there are are listed
+        assert_eq!(help("print_int(p.height);"), vec!["`P` has the fields `length`, `width`"]);
+        assert_eq!(help("print(Shape.Triangle);"), vec!["`Shape` has the variants `Circle`, `Square`"]);
+        assert!(help("print_int(p.size());").is_empty());
+    }
+}
@@ -1,31 +1,631 @@
-use crate::parse::*;
-use crate::Token;
+use std::collections::HashMap;
 
-pub fn validate(node: Node) -> bool {
-    match node.token {
-        Token::If => {
-            if node.children.len() < 2 {
-                return false;
+use crate::ast::*;
+use crate::diag::{self, Diagnostic};
+use crate::lex::Span;
+
+pub fn validate(program: &Program) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        structs: HashMap::new(),
+        enums: HashMap::new(),
+        loops: 0,
+        diagnostics: Vec::new(),
+    };
+
+    // duplicate and undefined names are reported by the resolver, which runs first
+    let mut main = None;
+
+    for item in &program.items {
+        match item {
+            Item::Fn(function) => {
+                if function.name.name == "main" {
+                    main = Some(function);
+                }
+            }
+            Item::Struct(decl) => {
+                validator.structs.entry(&decl.name.name).or_insert(decl);
+            }
+            Item::Enum(decl) => {
+                validator.enums.entry(&decl.name.name).or_insert(decl);
+            }
+        }
+    }
+
+    if main.is_none() {
+        validator.diagnostics.push(
+            Diagnostic::error("no `main` function")
+                .with_code(diag::MISSING_MAIN)
+                .with_note("a simpl program starts by calling `main`")
+                .with_help("add `fn main() { ... }`, where the program starts"),
+        );
+    }
+
+    for item in &program.items {
+        match item {
+            Item::Fn(function) => validator.validate_fn(function),
+            Item::Struct(decl) => {
+                for method in &decl.methods {
+                    validator.validate_fn(method);
+                }
+            }
+            Item::Enum(_) => {}
+        }
+    }
+
+    validator.diagnostics
+}
+
+struct Validator<'a> {
+    structs: HashMap<&'a str, &'a StructDecl>,
+    enums: HashMap<&'a str, &'a EnumDecl>,
+    // how many loops the statement being validated is nested in
+    loops: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    fn duplicate(&mut self, name: &Ident, first: Span) {
+        self.diagnostics.push(
+            Diagnostic::error(format!("`{}` is defined more than once", name.name))
+                .with_code(diag::DUPLICATE_DEFINITION)
+                .with_label(name.span, "defined again here")
+                .with_secondary(first, "first defined here"),
+        );
+    }
+
+    fn validate_fn(&mut self, function: &FnDecl) {
+        self.loops = 0;
+        self.validate_block(&function.body);
+    }
+
+    fn validate_block(&mut self, block: &Block) {
+        // the statement that jumped out of the block, and what kind of jump it was
+        let mut jumped = None;
+        for stmt in &block.stmts {
+            if let Some((span, keyword)) = jumped.take() {
+                self.diagnostics.push(
+                    Diagnostic::warning("unreachable statement")
+                        .with_label(stmt.span, "this is never run")
+                        .with_secondary(span, format!("because of this `{}`", keyword)),
+                );
+            }
+            match stmt.kind {
+                StmtKind::Return(_) => jumped = Some((stmt.span, "return")),
+                StmtKind::Break => jumped = Some((stmt.span, "break")),
+                StmtKind::Continue => jumped = Some((stmt.span, "continue")),
+                _ => {}
+            }
+
+            match &stmt.kind {
+                StmtKind::Let { value, .. } => {
+                    if let Some(value) = value {
+                        self.validate_expr(value);
+                    }
+                }
+                StmtKind::Assign { target, value } => {
+                    self.validate_expr(target);
+                    self.validate_expr(value);
+                }
+                StmtKind::If {
+                    branches,
+                    otherwise,
+                } => {
+                    for (cond, body) in branches {
+                        self.validate_expr(cond);
+                        self.validate_block(body);
+                    }
+                    if let Some(body) = otherwise {
+                        self.validate_block(body);
+                    }
+                }
+                StmtKind::While { cond, body } => {
+                    self.validate_expr(cond);
+                    self.loops += 1;
+                    self.validate_block(body);
+                    self.loops -= 1;
+                }
+                StmtKind::For { iter, body, .. } => {
+                    self.validate_expr(iter);
+                    self.loops += 1;
+                    self.validate_block(body);
+                    self.loops -= 1;
+                }
+                StmtKind::Match { scrutinee, arms } => self.validate_match(scrutinee, arms),
+                StmtKind::Return(value) => {
+                    if let Some(value) = value {
+                        self.validate_expr(value);
+                    }
+                }
+                StmtKind::Break | StmtKind::Continue => {
+                    if self.loops == 0 {
+                        let keyword = if stmt.kind == StmtKind::Break { "break" } else { "continue" };
+                        self.diagnostics.push(
+                            Diagnostic::error(format!("`{}` outside of a loop", keyword))
+                                .with_code(diag::OUTSIDE_LOOP)
+                                .with_label(stmt.span, format!("cannot `{}` outside of a loop", keyword)),
+                        );
+                    }
+                }
+                StmtKind::Block(body) => self.validate_block(body),
+                StmtKind::Expr(expr) => self.validate_expr(expr),
+            }
+        }
+    }
+
+    fn validate_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Int(_)
+            | ExprKind::Float(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Str(_)
+            | ExprKind::Null
+            | ExprKind::Ident(_) => {}
+            ExprKind::List(items) => {
+                for item in items {
+                    self.validate_expr(item);
+                }
+            }
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    self.validate_expr(key);
+                    self.validate_expr(value);
+                }
+            }
+            ExprKind::Unary { expr, .. } => self.validate_expr(expr),
+            ExprKind::Binary { lhs, rhs, .. } => {
+                self.validate_expr(lhs);
+                self.validate_expr(rhs);
+            }
+            ExprKind::Call { callee, args } => {
+                self.validate_expr(callee);
+                for arg in args {
+                    self.validate_expr(arg);
+                }
+            }
+            ExprKind::Field { expr, .. } => self.validate_expr(expr),
+            ExprKind::Index { expr, index } => {
+                self.validate_expr(expr);
+                self.validate_expr(index);
+            }
+            ExprKind::Range { start, end, .. } => {
+                self.validate_expr(start);
+                self.validate_expr(end);
+            }
+            ExprKind::StructLit { name, variant, fields } => {
+                for (_, value) in fields {
+                    self.validate_expr(value);
+                }
+                self.validate_struct_literal(expr.span, name, variant.as_ref(), fields);
+            }
+            ExprKind::Match { scrutinee, arms } => self.validate_match(scrutinee, arms),
+        }
+    }
+
+    // every field given must exist, and every declared field must be given exactly once. variants
+    // that don't exist or have no named fields are reported by the type checker
+    fn validate_struct_literal(&mut self, span: Span, name: &Ident, variant: Option<&Ident>, fields: &[(Ident, Expr)]) {
+        let (owner, declared_at, declared, methods) = match variant {
+            None => {
+                let Some(decl) = self.structs.get(name.name.as_str()).copied() else {
+                    return;
+                };
+                (name.name.clone(), decl.name.span, &decl.fields, &decl.methods[..])
+            }
+            Some(variant) => {
+                let found = self.enums.get(name.name.as_str()).and_then(|decl| {
+                    decl.variants.iter().find(|v| v.name.name == variant.name)
+                });
+                let Some(Variant {
+                    name: declared_at,
+                    fields: VariantFields::Struct(declared),
+                    ..
+                }) = found
+                else {
+                    return;
+                };
+                (format!("{}.{}", name.name, variant.name), declared_at.span, declared, &[][..])
+            }
+        };
+
+        let mut given: HashMap<&str, Span> = HashMap::new();
+
+        for (field, _) in fields {
+            if !declared.iter().any(|f| f.name.name == field.name) {
+                self.diagnostics.push(diag::unknown_field(&owner, declared_at, declared, methods, field));
+            } else if let Some(&first) = given.get(field.name.as_str()) {
+                self.duplicate(field, first);
+            } else {
+                given.insert(&field.name, field.span);
+            }
+        }
+
+        let missing: Vec<String> = declared
+            .iter()
+            .filter(|f| !given.contains_key(f.name.name.as_str()))
+            .map(|f| format!("`{}`", f.name.name))
+            .collect();
+        if !missing.is_empty() {
+            self.diagnostics.push(
+                Diagnostic::error(format!("missing {} in `{}` literal", missing.join(", "), owner))
+                    .with_code(diag::MISSING_FIELD)
+                    .with_label(span, "every field must be given a value"),
+            );
+        }
+    }
+
+    // arms must match values of the right shape, every value must be matched by some arm, and every
+    // arm must match something the arms before it don't
+    fn validate_match(&mut self, scrutinee: &Expr, arms: &[Arm]) {
+        self.validate_expr(scrutinee);
+        let mut rows = Vec::new();
+        for arm in arms {
+            rows.push(self.lower_pattern(&arm.pattern).map(|pat| vec![pat]));
+            match &arm.body {
+                ArmBody::Expr(expr) => self.validate_expr(expr),
+                ArmBody::Block(body) => self.validate_block(body),
             }
-            if !validate(node.children[0].clone()) {
-                return false;
+        }
+        // a pattern with a mistake in it has been reported already, and can't be reasoned about
+        let Some(rows) = rows.into_iter().collect::<Option<Vec<_>>>() else {
+            return;
+        };
+
+        for (i, arm) in arms.iter().enumerate() {
+            if self.useful(&rows[..i], &rows[i]) {
+                continue;
             }
-            if !validate(node.children[1].clone()) {
-                return false;
+            let diag = Diagnostic::warning("unreachable arm").with_label(arm.pattern.span, "this arm is never reached");
+            self.diagnostics.push(match arms[..i].iter().find(|earlier| earlier.pattern.is_catch_all()) {
+                Some(earlier) => diag.with_secondary(earlier.pattern.span, "this matches every value"),
+                None => diag.with_note("the arms before it already match everything it does"),
+            });
+        }
+
+        if !self.useful(&rows, &[Pat::Any]) {
+            return;
+        }
+        let missing: Vec<String> = match self.ctors(&rows) {
+            Some(ctors) => ctors
+                .iter()
+                .filter(|ctor| self.useful(&self.specialize(&rows, ctor), &vec![Pat::Any; self.arity(ctor)]))
+                .map(|ctor| format!("`{}`", self.describe(ctor)))
+                .collect(),
+            None => Vec::new(),
+        };
+        let diag = Diagnostic::error("non-exhaustive `match`").with_code(diag::NON_EXHAUSTIVE_MATCH);
+        self.diagnostics.push(if missing.is_empty() {
+            diag.with_label(scrutinee.span, "not every value is matched")
+                .with_help("add a `_` arm to match the rest")
+        } else {
+            diag.with_label(scrutinee.span, format!("{} not matched", missing.join(", ")))
+                .with_help("add an arm for each of them, or a `_` arm to match the rest")
+        });
+    }
+
+    // `None` when the pattern has a mistake, which is reported here, or names an enum or struct
+    // that doesn't exist, which the resolver reports
+    fn lower_pattern(&mut self, pattern: &Pattern) -> Option<Pat> {
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Binding(_) => Some(Pat::Any),
+            PatternKind::Literal(expr) => Some(Pat::Ctor(literal(expr), Vec::new())),
+            PatternKind::Variant { name, variant, fields } => {
+                let decl = self.enums.get(name.name.as_str()).copied()?;
+                let Some(index) = decl.variants.iter().position(|v| v.name.name == variant.name) else {
+                    self.diagnostics.push(diag::unknown_variant(decl, variant));
+                    return None;
+                };
+                let declared = &decl.variants[index];
+                let owner = format!("{}.{}", name.name, variant.name);
+                let args = match (fields, &declared.fields) {
+                    (PatternFields::Unit, VariantFields::Unit) => Some(Vec::new()),
+                    (PatternFields::Tuple(patterns), VariantFields::Tuple(types)) => {
+                        let args = self.lower_patterns(patterns);
+                        if patterns.len() != types.len() {
+                            let plural = if types.len() == 1 { "" } else { "s" };
+                            let (expected, found) = (types.len(), patterns.len());
+                            self.diagnostics.push(
+                                Diagnostic::error(format!(
+                                    "`{}` has {} field{} but the pattern has {}",
+                                    owner, expected, plural, found
+                                ))
+                                    .with_code(diag::WRONG_ARGUMENT_COUNT)
+                                    .with_label(pattern.span, format!("expected {} field{}", expected, plural))
+                                    .with_secondary(declared.span, format!("`{}` declared here", owner)),
+                            );
+                            return None;
+                        }
+                        args
+                    }
+                    (PatternFields::Struct(given), VariantFields::Struct(fields)) => {
+                        self.lower_fields(&owner, declared.name.span, (fields, &[]), given)
+                    }
+                    _ => {
+                        self.diagnostics.push(diag::wrong_shape(decl, declared, pattern.span));
+                        None
+                    }
+                };
+                Some(Pat::Ctor(Ctor::Variant(name.name.clone(), index), args?))
+            }
+            PatternKind::Struct { name, fields } => {
+                let decl = self.structs.get(name.name.as_str()).copied()?;
+                let args = self.lower_fields(&name.name, decl.name.span, (&decl.fields, &decl.methods), fields)?;
+                Some(Pat::Ctor(Ctor::Struct(name.name.clone()), args))
             }
         }
-        Token::Block => {
-            for child in node.children {
-                if !validate(child) {
-                    return false;
+    }
+
+    // every pattern is lowered, even after one fails, so that all of their mistakes are reported
+    fn lower_patterns(&mut self, patterns: &[Pattern]) -> Option<Vec<Pat>> {
+        let lowered: Vec<Option<Pat>> = patterns.iter().map(|pattern| self.lower_pattern(pattern)).collect();
+        lowered.into_iter().collect()
+    }
+
+    // the patterns for the fields of `owner` in declaration order, where any left out match anything
+    fn lower_fields(
+        &mut self,
+        owner: &str,
+        declared_at: Span,
+        (declared, methods): (&[Field], &[FnDecl]),
+        given: &[(Ident, Pattern)],
+    ) -> Option<Vec<Pat>> {
+        let mut args = vec![Some(Pat::Any); declared.len()];
+        let mut seen: HashMap<&str, Span> = HashMap::new();
+        let mut valid = true;
+        for (field, pattern) in given {
+            let lowered = self.lower_pattern(pattern);
+            match declared.iter().position(|f| f.name.name == field.name) {
+                None => {
+                    self.diagnostics.push(diag::unknown_field(owner, declared_at, declared, methods, field));
+                    valid = false;
                 }
+                Some(_) if seen.contains_key(field.name.as_str()) => {
+                    self.duplicate(field, seen[field.name.as_str()]);
+                    valid = false;
+                }
+                Some(i) => {
+                    seen.insert(&field.name, field.span);
+                    args[i] = lowered;
+                }
+            }
+        }
+        if !valid {
+            return None;
+        }
+        args.into_iter().collect()
+    }
+
+    // exhaustiveness and reachability both come down to whether a row of patterns matches some
+    // values that none of the rows before it do. the first column is split by how its values are
+    // built, and each way is checked against the rows that allow it, until no columns are left
+    fn useful(&self, rows: &[Vec<Pat>], row: &[Pat]) -> bool {
+        let Some((head, rest)) = row.split_first() else {
+            return rows.is_empty();
+        };
+        match head {
+            Pat::Ctor(ctor, args) => self.useful(&self.specialize(rows, ctor), &[&args[..], rest].concat()),
+            Pat::Any => match self.ctors(rows) {
+                Some(ctors) => ctors.iter().any(|ctor| {
+                    let row = [vec![Pat::Any; self.arity(ctor)], rest.to_vec()].concat();
+                    self.useful(&self.specialize(rows, ctor), &row)
+                }),
+                // a type with too many values to list is only covered by a pattern that matches anything
+                None => {
+                    let rows: Vec<Vec<Pat>> = rows
+                        .iter()
+                        .filter(|row| matches!(row[0], Pat::Any))
+                        .map(|row| row[1..].to_vec())
+                        .collect();
+                    self.useful(&rows, rest)
+                }
+            },
+        }
+    }
+
+    // the rows that match values built with `ctor`, with their first column replaced by the
+    // patterns for its fields
+    fn specialize(&self, rows: &[Vec<Pat>], ctor: &Ctor) -> Vec<Vec<Pat>> {
+        rows.iter()
+            .filter_map(|row| {
+                let head = match &row[0] {
+                    Pat::Ctor(other, args) if other == ctor => args.clone(),
+                    Pat::Ctor(..) => return None,
+                    Pat::Any => vec![Pat::Any; self.arity(ctor)],
+                };
+                Some([head, row[1..].to_vec()].concat())
+            })
+            .collect()
+    }
+
+    // every way of building a value of the type in the first column, when there are few enough
+    // to list. the type is taken from whichever pattern names one
+    fn ctors(&self, rows: &[Vec<Pat>]) -> Option<Vec<Ctor>> {
+        let first = rows.iter().find_map(|row| match &row[0] {
+            Pat::Ctor(ctor, _) => Some(ctor),
+            Pat::Any => None,
+        })?;
+        match first {
+            Ctor::Variant(name, _) => {
+                let count = self.enums[name.as_str()].variants.len();
+                Some((0..count).map(|i| Ctor::Variant(name.clone(), i)).collect())
             }
+            Ctor::Struct(name) => Some(vec![Ctor::Struct(name.clone())]),
+            Ctor::Bool(_) => Some(vec![Ctor::Bool(false), Ctor::Bool(true)]),
+            Ctor::Literal(_) => None,
+        }
+    }
+
+    fn arity(&self, ctor: &Ctor) -> usize {
+        match ctor {
+            Ctor::Variant(name, i) => match &self.enums[name.as_str()].variants[*i].fields {
+                VariantFields::Unit => 0,
+                VariantFields::Tuple(types) => types.len(),
+                VariantFields::Struct(fields) => fields.len(),
+            },
+            Ctor::Struct(name) => self.structs[name.as_str()].fields.len(),
+            Ctor::Bool(_) | Ctor::Literal(_) => 0,
         }
-        Token::Number(_) => {}
-        Token::Identifier(_) => {}
-        _ => {
-            return false;
+    }
+
+    // how a value built with `ctor` is written, with its fields left out
+    fn describe(&self, ctor: &Ctor) -> String {
+        match ctor {
+            Ctor::Variant(name, i) => {
+                let variant = &self.enums[name.as_str()].variants[*i];
+                let fields = match variant.fields {
+                    VariantFields::Unit => "",
+                    VariantFields::Tuple(_) => "(..)",
+                    VariantFields::Struct(_) => " { .. }",
+                };
+                format!("{}.{}{}", name, variant.name.name, fields)
+            }
+            Ctor::Struct(name) => format!("{} {{ .. }}", name),
+            Ctor::Bool(b) => b.to_string(),
+            Ctor::Literal(text) => text.clone(),
         }
     }
-    true
-}
\ No newline at end of file
+}
+
+// a pattern reduced to what exhaustiveness checking needs: it either matches anything, or values
+// built one particular way whose fields match the patterns given for them
+#[derive(Debug, Clone)]
+enum Pat {
+    Any,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+// the ways a value can be built. literals other than `true` and `false` stand for one value each
+// of a type with too many to list
+#[derive(Debug, PartialEq, Clone)]
+enum Ctor {
+    // an enum, and the position of one of its variants
+    Variant(String, usize),
+    Struct(String),
+    Bool(bool),
+    Literal(String),
+}
+
+fn literal(expr: &Expr) -> Ctor {
+    match &expr.kind {
+        ExprKind::Bool(b) => Ctor::Bool(*b),
+        ExprKind::Int(n) => Ctor::Literal(n.to_string()),
+        // ints and floats are compared as numbers, so a whole float is the same literal as the int
+        // it equals: `1.0` matches what `1` does, whether the value matched is an int or a float
+        ExprKind::Float(x) if x.fract() == 0.0 && x.abs() < i64::MAX as f64 => Ctor::Literal((*x as i64).to_string()),
+        ExprKind::Float(x) => Ctor::Literal(format!("{:?}", x)),
+        ExprKind::Str(s) => Ctor::Literal(format!("{:?}", s)),
+        ExprKind::Unary { expr, .. } => match literal(expr) {
+            // and `-0` is `0`
+            Ctor::Literal(text) if text == "0" => Ctor::Literal(text),
+            Ctor::Literal(text) => Ctor::Literal(format!("-{}", text)),
+            other => other,
+        },
+        _ => Ctor::Literal("null".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::program;
+
+    // what the validator reports for `match value { arms }`, where the value is one of a function's
+    // parameters, each as its message and the text of its first label
+    fn check(value: &str, arms: &str) -> Vec<String> {
+        let source = format!(
+            "enum Opt {{ Some(bool), None }}
+            enum Shape {{ Dot, Line(Opt, Opt) }}
+            struct P {{ int x; bool b; }}
+            fn f(Opt o, Shape s, P p, bool b, int n, float x) {{ match {} {{ {} }} }}
+            fn main() {{}}",
+            value, arms
+        );
+        let diagnostics = validate(&program(&source));
+        diagnostics.iter().map(|d| format!("{}: {}", d.message, d.labels[0].message)).collect()
+    }
+
+    #[test]
+    fn nested_variants() {
+        let all = "Opt.Some(true) => print(1), Opt.Some(false) => print(2), Opt.None => print(3),";
+        assert!(check("o", all).is_empty());
+        let missing = check("o", "Opt.Some(true) => print(1), Opt.None => print(3),");
+        assert_eq!(missing, ["non-exhaustive `match`: `Opt.Some(..)` not matched"]);
+        let again = check("o", "Opt.Some(_) => print(1), Opt.None => print(2), Opt.Some(true) => print(3),");
+        assert_eq!(again, ["unreachable arm: this arm is never reached"]);
+
+        let value = "s";
+        let lines = "Shape.Line(Opt.None, _) => print(1), Shape.Line(_, Opt.None) => print(2), Shape.Dot => print(3),";
+        assert_eq!(check(value, lines), ["non-exhaustive `match`: `Shape.Line(..)` not matched"]);
+        let rest = "Shape.Line(Opt.Some(_), Opt.Some(_)) => print(4),";
+        assert!(check(value, &format!("{} {}", lines, rest)).is_empty());
+    }
+
+    #[test]
+    fn struct_patterns() {
+        let value = "p";
+        let some = "P { b: true } => print(1), P { x: 0, b: false } => print(2),";
+        assert_eq!(check(value, some), ["non-exhaustive `match`: `P { .. }` not matched"]);
+        let all = "P { b: true } => print(1), P { b: false } => print(2),";
+        assert!(check(value, all).is_empty());
+        let again = format!("{} P {{ x: 1 }} => print(3),", all);
+        assert_eq!(check(value, &again), ["unreachable arm: this arm is never reached"]);
+    }
+
+    #[test]
+    fn bools_are_covered_by_both_values() {
+        assert!(check("b", "true => print(1), false => print(2),").is_empty());
+        assert_eq!(check("b", "true => print(1),"), ["non-exhaustive `match`: `false` not matched"]);
+        let twice = check("b", "true => print(1), true => print(2),");
+        assert_eq!(
+            twice,
+            ["unreachable arm: this arm is never reached", "non-exhaustive `match`: `false` not matched"]
+        );
+    }
+
+    #[test]
+    fn equal_numbers_are_the_same_literal() {
+        let unreachable = ["unreachable arm: this arm is never reached"];
+        assert_eq!(check("x", "1 => print(1), 1.0 => print(2), _ => print(3),"), unreachable);
+        assert_eq!(check("n", "-1.0 => print(1), -1 => print(2), _ => print(3),"), unreachable);
+        assert_eq!(check("x", "0 => print(1), -0.0 => print(2), _ => print(3),"), unreachable);
+        assert!(check("x", "1 => print(1), 1.5 => print(2), -1 => print(3), _ => print(4),").is_empty());
+    }
+
+    #[test]
+    fn literals_need_a_catch_all() {
+        let some = check("n", "0 => print(1), 1 => print(2),");
+        assert_eq!(some, ["non-exhaustive `match`: not every value is matched"]);
+        assert!(check("n", "0 => print(1), _ => print(2),").is_empty());
+        let again = check("n", "0 => print(1), 0 => print(2), n => print(n),");
+        assert_eq!(again, ["unreachable arm: this arm is never reached"]);
+        let after = check("n", "_ => print(1), 0 => print(2),");
+        assert_eq!(after, ["unreachable arm: this arm is never reached"]);
+    }
+
+    #[test]
+    fn break_and_continue_need_a_loop() {
+        let codes = |source: &str| validate(&program(source)).iter().filter_map(|d| d.code).collect::<Vec<_>>();
+        assert_eq!(codes("fn main() { break; }"), vec![diag::OUTSIDE_LOOP]);
+        assert_eq!(codes("fn main() { if true { continue; } }"), vec![diag::OUTSIDE_LOOP]);
+        assert_eq!(codes("fn main() { while true { } break; }"), vec![diag::OUTSIDE_LOOP]);
+        // a loop in the caller doesn't count
+        assert_eq!(codes("fn f() { break; } fn main() { while true { f(); } }"), vec![diag::OUTSIDE_LOOP]);
+
+        let inside = "fn f(bool b) {
+                while b { if b { break; } else { continue; } }
+                for i in 0..2 { { match b { true => { break; }, false => { continue; } } } }
+            }
+            fn main() {}";
+        assert!(codes(inside).is_empty());
+    }
+
+    #[test]
+    fn misspelled_fields_in_literals_get_a_suggestion() {
+        let source = "struct P { int length; int width; }
+            enum Opt { Some(P), None }
+            fn f(Opt o) { match o { Opt.Some(P { lenght: 1, width: _ }) => print(1), _ => print(2) } }
+            fn main() { P p = P { length: 1, widht: 2 }; }";
+        let diagnostics = validate(&program(source));
+        let help: Vec<_> = diagnostics.iter().flat_map(|d| d.help.clone()).collect();
+        assert_eq!(help, vec!["did you mean `length`?", "did you mean `width`?"]);
+    }
+}